@@ -0,0 +1,125 @@
+#![no_std]
+
+use soroban_sdk::{
+    contract, contracterror, contractimpl, contracttype, panic_with_error, symbol_short,
+    xdr::ToXdr, Address, BytesN, Env, Symbol,
+};
+
+mod test;
+
+const DAY_IN_LEDGERS: u32 = 17_280;
+const INSTANCE_THRESHOLD: u32 = 7 * DAY_IN_LEDGERS;
+const INSTANCE_EXTEND_TO: u32 = 30 * DAY_IN_LEDGERS;
+const PERSISTENT_THRESHOLD: u32 = 30 * DAY_IN_LEDGERS;
+const PERSISTENT_EXTEND_TO: u32 = 120 * DAY_IN_LEDGERS;
+
+const TOPIC: Symbol = symbol_short!("zafegard");
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageKey {
+    Admin,
+    // Code of the policies deployed from now on
+    PolicyWasm,
+    // Policy deployed for a smart wallet
+    Policy(Address),
+}
+
+#[contracterror]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u32)]
+pub enum Error {
+    NotInitialized = 1,
+    AlreadyDeployed = 2,
+}
+
+#[contract]
+pub struct Contract;
+
+#[contractimpl]
+impl Contract {
+
+    pub fn __constructor(env: Env, admin: Address, policy_wasm: BytesN<32>) {
+        env.storage().instance().set(&StorageKey::Admin, &admin);
+        env.storage().instance().set(&StorageKey::PolicyWasm, &policy_wasm);
+        self::extend_instance(&env);
+    }
+    // Deploys the policy of the wallet, with the wallet as its admin. Anyone can
+    // call it since the address and the admin only depend on the wallet
+    pub fn deploy(env: Env, wallet: Address) -> Address {
+        self::extend_instance(&env);
+
+        let key = StorageKey::Policy(wallet.clone());
+        if env.storage().persistent().has(&key) {
+            panic_with_error!(&env, Error::AlreadyDeployed)
+        }
+
+        let policy_wasm = env
+            .storage()
+            .instance()
+            .get::<StorageKey, BytesN<32>>(&StorageKey::PolicyWasm)
+            .unwrap_or_else(|| panic_with_error!(&env, Error::NotInitialized));
+
+        // The constructor runs in the same call, so nobody can claim the policy first
+        let policy = env
+            .deployer()
+            .with_current_contract(self::salt(&env, &wallet))
+            .deploy_v2(policy_wasm, (wallet.clone(), ));
+
+        env.storage().persistent().set(&key, &policy);
+        self::extend_persistent(&env, &key);
+
+        env.events().publish((TOPIC, symbol_short!("deployed"), wallet), policy.clone());
+
+        policy
+    }
+    // Only affects the policies deployed afterwards
+    pub fn set_policy_wasm(env: Env, policy_wasm: BytesN<32>) {
+        self::get_admin_address(&env).require_auth();
+        self::extend_instance(&env);
+
+        env.storage().instance().set(&StorageKey::PolicyWasm, &policy_wasm);
+    }
+    pub fn get_policy(env: Env, wallet: Address) -> Option<Address> {
+        let key = StorageKey::Policy(wallet);
+        let policy = env.storage().persistent().get::<StorageKey, Address>(&key);
+
+        if policy.is_some() {
+            self::extend_persistent(&env, &key);
+        }
+
+        policy
+    }
+    // Where the policy of the wallet is or will be deployed
+    pub fn policy_address(env: Env, wallet: Address) -> Address {
+        env.deployer()
+            .with_current_contract(self::salt(&env, &wallet))
+            .deployed_address()
+    }
+    pub fn get_admin(env: Env) -> Address {
+        self::get_admin_address(&env)
+    }
+}
+
+fn salt(env: &Env, wallet: &Address) -> BytesN<32> {
+    env.crypto().sha256(&wallet.clone().to_xdr(env)).to_bytes()
+}
+
+fn get_admin_address(env: &Env) -> Address {
+    env.storage()
+        .instance()
+        .get::<StorageKey, Address>(&StorageKey::Admin)
+        .unwrap_or_else(|| panic_with_error!(env, Error::NotInitialized))
+}
+
+fn extend_instance(env: &Env) {
+    env.storage()
+        .instance()
+        .extend_ttl(INSTANCE_THRESHOLD, INSTANCE_EXTEND_TO);
+}
+
+fn extend_persistent(env: &Env, key: &StorageKey) {
+    env.storage()
+        .persistent()
+        .extend_ttl(key, PERSISTENT_THRESHOLD, PERSISTENT_EXTEND_TO);
+}
@@ -0,0 +1,102 @@
+#![cfg(test)]
+
+extern crate std;
+
+use soroban_sdk::{
+    testutils::{Address as _, BytesN as _, EnvTestConfig},
+    Address,
+    BytesN,
+    Env,
+};
+
+#[cfg(feature = "policy-wasm")]
+use soroban_sdk::Error as SorobanError;
+
+use crate::{Contract, ContractClient};
+
+#[cfg(feature = "policy-wasm")]
+use crate::Error;
+
+// Built by `make test` before running the tests that deploy it
+#[cfg(feature = "policy-wasm")]
+mod policy {
+    soroban_sdk::contractimport!(file = "../target/wasm32-unknown-unknown/release/zafegard_policy.wasm");
+}
+
+#[test]
+fn policy_addresses_are_known_upfront() {
+    let mut env = Env::default();
+    env.set_config(EnvTestConfig {
+        capture_snapshot_at_drop: false,
+    });
+
+    env.mock_all_auths();
+
+    let admin = Address::generate(&env);
+    let factory_address = env.register(Contract, (admin.clone(), BytesN::<32>::random(&env)));
+    let factory_client = ContractClient::new(&env, &factory_address);
+
+    assert_eq!(factory_client.get_admin(), admin);
+
+    let wallet = Address::generate(&env);
+    let other_wallet = Address::generate(&env);
+
+    assert_eq!(factory_client.get_policy(&wallet), None);
+
+    // Only depends on the wallet, not on the policy code
+    let expected_address = factory_client.policy_address(&wallet);
+    assert_eq!(factory_client.policy_address(&wallet), expected_address);
+    assert_ne!(factory_client.policy_address(&other_wallet), expected_address);
+
+    factory_client.set_policy_wasm(&BytesN::<32>::random(&env));
+    assert_eq!(env.auths()[0].0, admin);
+    assert_eq!(factory_client.policy_address(&wallet), expected_address);
+
+    // Nobody else can change the code
+    env.set_auths(&[]);
+
+    let failed_set = factory_client.try_set_policy_wasm(&BytesN::<32>::random(&env));
+    assert!(failed_set.is_err());
+}
+
+#[cfg(feature = "policy-wasm")]
+#[test]
+fn deploys_one_policy_per_wallet() {
+    let mut env = Env::default();
+    env.set_config(EnvTestConfig {
+        capture_snapshot_at_drop: false,
+    });
+
+    env.mock_all_auths();
+
+    let policy_wasm = env.deployer().upload_contract_wasm(policy::WASM);
+
+    let admin = Address::generate(&env);
+    let factory_address = env.register(Contract, (admin.clone(), policy_wasm));
+    let factory_client = ContractClient::new(&env, &factory_address);
+
+    let wallet = Address::generate(&env);
+
+    assert_eq!(factory_client.get_policy(&wallet), None);
+
+    // The address is known before the deployment
+    let expected_address = factory_client.policy_address(&wallet);
+    let policy_address = factory_client.deploy(&wallet);
+
+    assert_eq!(policy_address, expected_address);
+    assert_eq!(factory_client.get_policy(&wallet), Some(policy_address.clone()));
+
+    // Initialized in the same call, with the wallet as its admin
+    let policy_client = policy::Client::new(&env, &policy_address);
+    assert_eq!(policy_client.get_admin(), wallet);
+
+    let failed_deploy = factory_client.try_deploy(&wallet);
+    assert_eq!(failed_deploy, Err(Ok(SorobanError::from(Error::AlreadyDeployed))));
+
+    // Every wallet gets its own policy
+    let other_wallet = Address::generate(&env);
+    let other_policy_address = factory_client.deploy(&other_wallet);
+
+    assert_ne!(other_policy_address, policy_address);
+    assert_eq!(factory_client.get_policy(&other_wallet), Some(other_policy_address));
+}
@@ -0,0 +1,165 @@
+use smart_wallet_interface::types::SignerKey;
+use soroban_sdk::{contracttype, symbol_short, Address, BytesN, Env, Symbol, Vec};
+
+use crate::{TtlConfig, VaultAllowance};
+
+// Every event is published under this first topic. Signer keys are vectors,
+// which can't be topics, so events only carry them in their data
+const TOPIC: Symbol = symbol_short!("zafegard");
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdminChangedEvent {
+    pub previous: Option<Address>,
+    pub admin: Address,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdminTransferEvent {
+    pub admin: Address,
+    pub new_admin: Address,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuardianChangedEvent {
+    pub guardian: Option<Address>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PauseEvent {
+    pub by: Address,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalletEvent {
+    pub wallet: Address,
+    pub user: SignerKey,
+    pub allowances: Vec<VaultAllowance>,
+    pub expiration: Option<u32>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserEvent {
+    pub wallet: Address,
+    pub user: SignerKey,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepositEvent {
+    pub wallet: Address,
+    pub user: SignerKey,
+    pub vault: Address,
+    pub amounts: Vec<i128>,
+    pub ledger: u32,
+    pub timestamp: u64,
+}
+
+pub fn admin_changed(env: &Env, previous: Option<Address>, admin: Address) {
+    env.events().publish(
+        (TOPIC, symbol_short!("admin")),
+        AdminChangedEvent { previous, admin },
+    );
+}
+
+pub fn admin_proposed(env: &Env, admin: Address, new_admin: Address) {
+    env.events().publish(
+        (TOPIC, symbol_short!("proposed")),
+        AdminTransferEvent { admin, new_admin },
+    );
+}
+
+pub fn admin_transfer_cancelled(env: &Env, admin: Address, new_admin: Address) {
+    env.events().publish(
+        (TOPIC, symbol_short!("cancelled")),
+        AdminTransferEvent { admin, new_admin },
+    );
+}
+
+pub fn guardian_changed(env: &Env, guardian: Option<Address>) {
+    env.events().publish(
+        (TOPIC, symbol_short!("guardian")),
+        GuardianChangedEvent { guardian },
+    );
+}
+
+pub fn paused(env: &Env, by: Address) {
+    env.events().publish((TOPIC, symbol_short!("paused")), PauseEvent { by });
+}
+
+pub fn unpaused(env: &Env, by: Address) {
+    env.events().publish((TOPIC, symbol_short!("unpaused")), PauseEvent { by });
+}
+
+pub fn upgraded(env: &Env, wasm_hash: BytesN<32>) {
+    env.events().publish((TOPIC, symbol_short!("upgraded")), wasm_hash);
+}
+
+pub fn ttl_config_changed(env: &Env, config: TtlConfig) {
+    env.events().publish((TOPIC, symbol_short!("ttl")), config);
+}
+
+pub fn wallet_added(env: &Env, wallet: Address, user: SignerKey, allowances: Vec<VaultAllowance>, expiration: Option<u32>) {
+    env.events().publish(
+        (TOPIC, symbol_short!("added"), wallet.clone()),
+        WalletEvent { wallet, user, allowances, expiration },
+    );
+}
+
+pub fn wallet_updated(env: &Env, wallet: Address, user: SignerKey, allowances: Vec<VaultAllowance>, expiration: Option<u32>) {
+    env.events().publish(
+        (TOPIC, symbol_short!("updated"), wallet.clone()),
+        WalletEvent { wallet, user, allowances, expiration },
+    );
+}
+
+pub fn wallet_removed(env: &Env, wallet: Address, user: SignerKey) {
+    env.events().publish(
+        (TOPIC, symbol_short!("removed"), wallet.clone()),
+        UserEvent { wallet, user },
+    );
+}
+
+pub fn wallet_frozen(env: &Env, wallet: Address, user: SignerKey) {
+    env.events().publish(
+        (TOPIC, symbol_short!("frozen"), wallet.clone()),
+        UserEvent { wallet, user },
+    );
+}
+
+pub fn wallet_unfrozen(env: &Env, wallet: Address, user: SignerKey) {
+    env.events().publish(
+        (TOPIC, symbol_short!("unfrozen"), wallet.clone()),
+        UserEvent { wallet, user },
+    );
+}
+
+pub fn migrated(env: &Env, wallet: Address, user: SignerKey) {
+    env.events().publish(
+        (TOPIC, symbol_short!("migrated"), wallet.clone()),
+        UserEvent { wallet, user },
+    );
+}
+
+pub fn migration_finished(env: &Env, version: u32) {
+    env.events().publish((TOPIC, symbol_short!("finished")), version);
+}
+
+pub fn deposit(env: &Env, wallet: Address, user: SignerKey, vault: Address, amounts: Vec<i128>) {
+    env.events().publish(
+        (TOPIC, symbol_short!("deposit"), wallet.clone()),
+        DepositEvent {
+            wallet,
+            user,
+            vault,
+            amounts,
+            ledger: env.ledger().sequence(),
+            timestamp: env.ledger().timestamp(),
+        },
+    );
+}
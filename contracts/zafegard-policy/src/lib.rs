@@ -1,34 +1,120 @@
 #![no_std]
 
 use smart_wallet_interface::{
-    types::{Signer, SignerExpiration, SignerKey, SignerLimits, SignerStorage},
+    types::{SignerExpiration, SignerKey, SignerLimits, SignerStorage},
     PolicyInterface, SmartWalletClient,
 };
+use standing::Standing;
+use types::SaverSigner;
 use soroban_sdk::{
     auth::{Context, ContractContext},
-    contract, contracterror, contractimpl, contracttype, map, panic_with_error, symbol_short, vec,
-    Address, BytesN, Env, TryFromVal, Vec,
+    contract, contracterror, contractimpl, contracttype, panic_with_error, symbol_short, vec,
+    Address, BytesN, Env, Map, Symbol, TryFromVal, Val, Vec,
 };
-
+use common::models::AssetStrategySet;
+
+mod events;
+mod migration;
+mod schedule;
+mod standing;
+mod storage;
+mod ttl;
 mod types;
 
 mod test;
 
-const MONTH_IN_LEDGERS: u32 = 3600*24*30/5;
+const MAX_BPS: u32 = 10_000;
+// Bounds the size of a rolling window's spent ledger
+const MAX_WINDOW_DEPOSITS: u32 = 32;
 
 #[contracttype]
 #[derive(Clone, Debug, PartialEq)]
 pub enum StorageKey {
     Admin,
-    Previous(BytesN<32>),
-    VaultAllowance(BytesN<32>),
+    // Admin proposed by the current one, until it accepts
+    PendingAdmin,
+    // Can pause the policy, but only the admin can unpause it
+    Guardian,
+    Paused,
+    TtlConfig,
+    // Layout of the stored entries, see migration::STORAGE_VERSION
+    Version,
+    // User entries are keyed by the smart wallet first, so that every wallet
+    // manages its own signers
+    // Timestamp of the user's last deposit into a vault
+    Previous(Address, SignerKey, Address),
+    // Map of vault to VaultAllowance
+    Allowances(Address, SignerKey),
+    // Deposits into a vault still inside a rolling window
+    Spent(Address, SignerKey, Address),
+    // Suspended users keep their configuration but can't deposit
+    Frozen(Address, SignerKey),
+    // Last ledger sequence at which the user can deposit
+    Expiration(Address, SignerKey),
+    // Storage of the user entries, only set when temporary
+    Storage(Address, SignerKey),
 }
 
 #[contracttype]
 #[derive(Clone, Debug, PartialEq)]
 pub struct VaultAllowance {
     pub vault: Address,
-    pub amount: i128,
+    // Maximum deposit for each of the vault assets, in the vault's asset order.
+    // Checked against the vault's get_assets when the allowance is stored
+    pub amounts: Vec<i128>,
+    pub schedule: Schedule,
+    // Maximum accepted slippage between amounts and min_amounts, in basis points
+    pub max_slippage_bps: u32,
+    // Number of missed periods whose amounts can still be deposited later
+    pub max_carry_over: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schedule {
+    // Minimum number of seconds between two deposits
+    Interval(u64),
+    // One deposit per calendar month, on or after the given day (UTC)
+    Monthly(u32),
+    // Any number of deposits, as long as they add up to the amounts within
+    // the given number of seconds
+    Rolling(u64),
+}
+
+// Entries are extended to `*_extend_to` ledgers once their TTL drops below `*_threshold`
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct TtlConfig {
+    pub instance_threshold: u32,
+    pub instance_extend_to: u32,
+    pub persistent_threshold: u32,
+    pub persistent_extend_to: u32,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Eligibility {
+    pub vault: Address,
+    pub last_deposit: Option<u64>,
+    // Earliest timestamp at which policy__ allows a deposit
+    pub next_eligible: u64,
+    // Maximum deposit policy__ allows right now for each of the vault assets
+    pub remaining: Vec<i128>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Verdict {
+    pub allowed: bool,
+    // Every rule policy__ would reject the deposit for, in the order it checks them
+    pub failures: Vec<Error>,
+}
+
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Spend {
+    pub timestamp: u64,
+    pub amounts: Vec<i128>,
 }
 
 
@@ -45,6 +131,22 @@ pub enum Error {
     WrongVault = 7,
     WrongContract = 8,
     UnexpectedError = 9,
+    InvalidSchedule = 10,
+    InvalidAmount = 11,
+    WrongAssetCount = 12,
+    InvalidAllowances = 13,
+    WrongFrom = 14,
+    InvalidSlippage = 15,
+    SlippageTooHigh = 16,
+    TooManyDeposits = 17,
+    NoPendingAdmin = 18,
+    Unauthorized = 19,
+    Paused = 20,
+    Frozen = 21,
+    InvalidTtl = 22,
+    Expired = 23,
+    InvalidExpiration = 24,
+    Lapsed = 25,
 }
 
 #[contract]
@@ -53,68 +155,632 @@ pub struct Contract;
 #[contractimpl]
 impl Contract {
 
-    pub fn init(env: Env, admin: Address) {
-        if env.storage().instance().has(&StorageKey::Admin) {
-            panic_with_error!(&env, Error::AlreadyInitialized)
+    // Runs atomically with the deployment, so nobody can claim the admin role first
+    pub fn __constructor(env: Env, admin: Address) {
+        env.storage().instance().set(&StorageKey::Admin, &admin);
+        env.storage().instance().set(&StorageKey::Version, &migration::STORAGE_VERSION);
+        ttl::extend_instance(&env);
+
+        events::admin_changed(&env, None, admin);
+    }
+    // The signer stops working after the `expiration` ledger sequence, if any. The
+    // policy keeps the user entries in the same storage as the signer
+    pub fn add_wallet(
+        env: Env,
+        wallet: Address,
+        signer: SaverSigner,
+        allowances: Vec<VaultAllowance>,
+        expiration: Option<u32>,
+        storage: SignerStorage,
+    ) {
+        wallet.require_auth();
+        ttl::extend_instance(&env);
+
+        let user = signer.key();
+        let old_storage = storage::user_storage(&env, &wallet, &user);
+        // Adding the user again must not lift a freeze set by the admin
+        if self::is_frozen(&env, &old_storage, &wallet, &user) {
+            panic_with_error!(&env, Error::Frozen)
+        }
+        // The signer may have left the wallet without going through remove_wallet, its
+        // cooldowns and expiration don't carry over to the new allowances. Both storages
+        // are cleared since the user can switch from one to the other
+        for old_storage in [SignerStorage::Persistent, SignerStorage::Temporary] {
+            self::remove_user_state(&env, &old_storage, &wallet, &user);
         }
 
-        env.storage().instance().set(&StorageKey::Admin, &admin);
+        let allowances = self::to_allowance_map(&env, allowances);
+
+        let storage_key = StorageKey::Storage(wallet.clone(), user.clone());
+        match storage {
+            SignerStorage::Persistent => env.storage().persistent().remove(&storage_key),
+            SignerStorage::Temporary => env.storage().persistent().set(&storage_key, &storage),
+        }
+
+        let expiration_key = StorageKey::Expiration(wallet.clone(), user.clone());
+        match expiration {
+            Some(expiration) => {
+                if expiration < env.ledger().sequence() {
+                    panic_with_error!(&env, Error::InvalidExpiration)
+                }
+                storage::set(&env, &storage, &expiration_key, &expiration);
+            }
+            None => storage::remove(&env, &storage, &expiration_key),
+        }
+
+        SmartWalletClient::new(&env, &wallet).add_signer(&signer.to_signer(
+            SignerExpiration(expiration),
+            self::signer_limits(&env, &allowances.keys()),
+            storage.clone(),
+        ));
+        let key = StorageKey::Allowances(wallet.clone(), user.clone());
+
+        storage::set::<Map<Address, VaultAllowance>>(&env, &storage, &key, &allowances);
+        ttl::extend_user(&env, &wallet, &user);
+
+        events::wallet_added(&env, wallet, user, allowances.values(), expiration);
+    }
+    pub fn remove_wallet(env: Env, wallet: Address, user: SignerKey) {
+        wallet.require_auth();
+        ttl::extend_instance(&env);
+
+        let storage = storage::user_storage(&env, &wallet, &user);
+        // Only the admin can lift a freeze, removing the user would drop it
+        if self::is_frozen(&env, &storage, &wallet, &user) {
+            panic_with_error!(&env, Error::Frozen)
+        }
+
+        // Users whose temporary entries lapsed only have their signer left
+        match self::find_allowances(&env, &storage, &wallet, &user) {
+            Ok(_) | Err(Error::Lapsed) => {}
+            Err(error) => panic_with_error!(&env, error),
+        }
+
+        SmartWalletClient::new(&env, &wallet).remove_signer(&user);
+
+        self::remove_user_state(&env, &storage, &wallet, &user);
+        env.storage().persistent().remove(&StorageKey::Storage(wallet.clone(), user.clone()));
+
+        events::wallet_removed(&env, wallet, user);
     }
-    pub fn add_wallet(env: Env, user: BytesN<32>, vault: Address, amount: i128) {
+    // Replaces every vault allowance of the user, keeping its expiration and storage
+    pub fn update_wallet(env: Env, wallet: Address, signer: SaverSigner, allowances: Vec<VaultAllowance>) {
+        wallet.require_auth();
+        ttl::extend_instance(&env);
+
+        let user = signer.key();
+        let storage = storage::user_storage(&env, &wallet, &user);
+        let key = StorageKey::Allowances(wallet.clone(), user.clone());
+        let old_allowances = self::get_allowances(&env, &wallet, &user);
+        let expiration = self::get_expiration(&env, &storage, &wallet, &user);
+
+        let allowances = self::to_allowance_map(&env, allowances);
+
+        // The signer is only allowed to call the vaults it was registered with
+        if allowances.keys() != old_allowances.keys() {
+            SmartWalletClient::new(&env, &wallet).update_signer(&signer.to_signer(
+                SignerExpiration(expiration),
+                self::signer_limits(&env, &allowances.keys()),
+                storage.clone(),
+            ));
+        }
+
+        for vault in old_allowances.keys().iter() {
+            if !allowances.contains_key(vault.clone()) {
+                self::remove_vault_state(&env, &storage, &wallet, &user, vault);
+            }
+        }
+
+        storage::set::<Map<Address, VaultAllowance>>(&env, &storage, &key, &allowances);
+        ttl::extend_user(&env, &wallet, &user);
+
+        events::wallet_updated(&env, wallet, user, allowances.values(), expiration);
+    }
+
+    pub fn propose_admin(env: Env, new_admin: Address) {
         let admin = self::get_admin_address(&env);
 
         admin.require_auth();
+        ttl::extend_instance(&env);
 
-        SmartWalletClient::new(&env, &admin).add_signer(&Signer::Ed25519(
-            user.clone(),
-            SignerExpiration(None),
-            SignerLimits(Some(map![
-                &env,
-                (
-                    vault.clone(),
-                    Some(vec![
-                        &env,
-                        SignerKey::Policy(env.current_contract_address())
-                    ])
-                )
-            ])),
-            SignerStorage::Persistent,
-        ));
-        let vault_allowance = VaultAllowance {  
-            vault: vault,
-            amount: amount,
-        };
-        let key = StorageKey::VaultAllowance(user.clone());
+        env.storage().instance().set(&StorageKey::PendingAdmin, &new_admin);
+
+        events::admin_proposed(&env, admin, new_admin);
+    }
+    pub fn accept_admin(env: Env) {
+        let admin = self::get_admin_address(&env);
+        let new_admin = self::get_pending_admin_address(&env);
+
+        new_admin.require_auth();
+        ttl::extend_instance(&env);
+
+        env.storage().instance().set(&StorageKey::Admin, &new_admin);
+        env.storage().instance().remove(&StorageKey::PendingAdmin);
 
-        env.storage()
-        .persistent()
-        .set::<StorageKey, VaultAllowance>(&key, &vault_allowance);
+        events::admin_changed(&env, Some(admin), new_admin);
     }
-    pub fn remove_wallet(env: Env, user: BytesN<32>) {
+    pub fn cancel_admin_transfer(env: Env) {
         let admin = self::get_admin_address(&env);
 
         admin.require_auth();
+        ttl::extend_instance(&env);
 
-        SmartWalletClient::new(&env, &admin).remove_signer(&SignerKey::Ed25519(user.clone()));
+        let new_admin = self::get_pending_admin_address(&env);
+        env.storage().instance().remove(&StorageKey::PendingAdmin);
 
-        env.storage().persistent().remove(&user);
+        events::admin_transfer_cancelled(&env, admin, new_admin);
     }
-    pub fn update_wallet(env: Env, user: BytesN<32>, vault: Option<Address>, amount: Option<i128>) {
+    // Suspends a single user without touching its allowances or deposit history
+    pub fn freeze_wallet(env: Env, wallet: Address, user: SignerKey) {
         self::get_admin_address(&env).require_auth();
+        ttl::extend_instance(&env);
 
-        let (old_vault, old_amount) = env
-            .storage()
-            .persistent()
-            .get::<BytesN<32>, (Address, i128)>(&user)
-            .unwrap_or_else(|| panic_with_error!(&env, Error::NotFound));
+        self::get_allowances(&env, &wallet, &user);
+        let storage = storage::user_storage(&env, &wallet, &user);
+        storage::set(&env, &storage, &StorageKey::Frozen(wallet.clone(), user.clone()), &true);
+        ttl::extend_user(&env, &wallet, &user);
 
-        let amount = amount.unwrap_or(old_amount);
-        let vault = vault.unwrap_or(old_vault);
+        events::wallet_frozen(&env, wallet, user);
+    }
+    pub fn unfreeze_wallet(env: Env, wallet: Address, user: SignerKey) {
+        self::get_admin_address(&env).require_auth();
+        ttl::extend_instance(&env);
+
+        self::get_allowances(&env, &wallet, &user);
+        let storage = storage::user_storage(&env, &wallet, &user);
+        storage::remove(&env, &storage, &StorageKey::Frozen(wallet.clone(), user.clone()));
+        ttl::extend_user(&env, &wallet, &user);
+
+        events::wallet_unfrozen(&env, wallet, user);
+    }
+    pub fn is_frozen(env: Env, wallet: Address, user: SignerKey) -> bool {
+        let storage = storage::user_storage(&env, &wallet, &user);
+
+        self::is_frozen(&env, &storage, &wallet, &user)
+    }
+    pub fn set_guardian(env: Env, guardian: Option<Address>) {
+        self::get_admin_address(&env).require_auth();
+        ttl::extend_instance(&env);
+
+        match &guardian {
+            Some(guardian) => env.storage().instance().set(&StorageKey::Guardian, guardian),
+            None => env.storage().instance().remove(&StorageKey::Guardian),
+        }
+
+        events::guardian_changed(&env, guardian);
+    }
+    // Stops every deposit until the admin unpauses
+    pub fn pause(env: Env, caller: Address) {
+        caller.require_auth();
+
+        let guardian = env.storage().instance().get::<StorageKey, Address>(&StorageKey::Guardian);
+        if caller != self::get_admin_address(&env) && Some(caller.clone()) != guardian {
+            panic_with_error!(&env, Error::Unauthorized)
+        }
+
+        env.storage().instance().set(&StorageKey::Paused, &true);
+        ttl::extend_instance(&env);
+
+        events::paused(&env, caller);
+    }
+    pub fn unpause(env: Env) {
+        let admin = self::get_admin_address(&env);
+
+        admin.require_auth();
+        ttl::extend_instance(&env);
+
+        env.storage().instance().remove(&StorageKey::Paused);
+
+        events::unpaused(&env, admin);
+    }
+    pub fn is_paused(env: Env) -> bool {
+        self::is_paused(&env)
+    }
+    pub fn get_guardian(env: Env) -> Option<Address> {
+        env.storage().instance().get::<StorageKey, Address>(&StorageKey::Guardian)
+    }
+    pub fn set_ttl_config(env: Env, config: TtlConfig) {
+        self::get_admin_address(&env).require_auth();
+
+        if config.instance_threshold > config.instance_extend_to
+            || config.persistent_threshold > config.persistent_extend_to
+            || config.instance_extend_to > env.storage().max_ttl()
+            || config.persistent_extend_to > env.storage().max_ttl()
+        {
+            panic_with_error!(&env, Error::InvalidTtl)
+        }
+
+        env.storage().instance().set(&StorageKey::TtlConfig, &config);
+        ttl::extend_instance(&env);
+
+        events::ttl_config_changed(&env, config);
+    }
+    pub fn get_ttl_config(env: Env) -> TtlConfig {
+        ttl::ttl_config(&env)
+    }
+    // For keepers, so that inactive users don't get archived
+    pub fn bump_user(env: Env, wallet: Address, user: SignerKey) {
+        self::get_admin_address(&env).require_auth();
+
+        self::get_allowances(&env, &wallet, &user);
+        ttl::extend_instance(&env);
+        ttl::extend_user(&env, &wallet, &user);
+    }
+    // Extends the contract instance and every listed user of the wallet
+    pub fn bump_all(env: Env, wallet: Address, users: Vec<SignerKey>) {
+        self::get_admin_address(&env).require_auth();
+
+        ttl::extend_instance(&env);
+        for user in users.iter() {
+            ttl::extend_user(&env, &wallet, &user);
+        }
+    }
+    // Replaces the contract code, keeping its address and storage. Call migrate
+    // afterwards if the new code bumps the storage version
+    pub fn upgrade(env: Env, wasm_hash: BytesN<32>) {
+        self::get_admin_address(&env).require_auth();
+        ttl::extend_instance(&env);
+
+        env.deployer().update_current_contract_wasm(wasm_hash.clone());
+
+        events::upgraded(&env, wasm_hash);
+    }
+    // Converts the entries of the listed users of a wallet to the current storage
+    // layout, in batches if there are too many users for a single transaction.
+    // Version 0 served a single wallet, the admin
+    pub fn migrate(env: Env, wallet: Address, users: Vec<BytesN<32>>) {
+        self::get_admin_address(&env).require_auth();
+        ttl::extend_instance(&env);
+
+        for user in users.iter() {
+            let key = SignerKey::Ed25519(user.clone());
+
+            migration::migrate_user(&env, &wallet, &user);
+            ttl::extend_user(&env, &wallet, &key);
+
+            events::migrated(&env, wallet.clone(), key);
+        }
+    }
+    // Marks the migration as done once every user went through migrate
+    pub fn finish_migration(env: Env) {
+        self::get_admin_address(&env).require_auth();
+        ttl::extend_instance(&env);
+
+        env.storage().instance().set(&StorageKey::Version, &migration::STORAGE_VERSION);
+
+        events::migration_finished(&env, migration::STORAGE_VERSION);
+    }
+    pub fn get_version(env: Env) -> u32 {
+        migration::storage_version(&env)
+    }
+    pub fn get_admin(env: Env) -> Address {
+        self::get_admin_address(&env)
+    }
+    pub fn get_pending_admin(env: Env) -> Option<Address> {
+        env.storage().instance().get::<StorageKey, Address>(&StorageKey::PendingAdmin)
+    }
+    pub fn get_allowance(env: Env, wallet: Address, user: SignerKey) -> Vec<VaultAllowance> {
+        self::get_allowances(&env, &wallet, &user).values()
+    }
+    pub fn get_expiration(env: Env, wallet: Address, user: SignerKey) -> Option<u32> {
+        self::get_allowances(&env, &wallet, &user);
+        let storage = storage::user_storage(&env, &wallet, &user);
 
-        // env.storage().persistent().set(&user, &(vault, amount));
+        self::get_expiration(&env, &storage, &wallet, &user)
+    }
+    pub fn get_storage(env: Env, wallet: Address, user: SignerKey) -> SignerStorage {
+        self::get_allowances(&env, &wallet, &user);
+
+        storage::user_storage(&env, &wallet, &user)
+    }
+    pub fn get_last_deposit(env: Env, wallet: Address, user: SignerKey, vault: Address) -> Option<u64> {
+        self::get_vault_allowance(&env, &wallet, &user, &vault);
+        let storage = storage::user_storage(&env, &wallet, &user);
+
+        storage::get::<u64>(&env, &storage, &StorageKey::Previous(wallet, user, vault))
+    }
+    pub fn next_eligible(env: Env, wallet: Address, user: SignerKey, vault: Address) -> u64 {
+        let allowance = self::get_vault_allowance(&env, &wallet, &user, &vault);
+        let storage = storage::user_storage(&env, &wallet, &user);
+
+        Standing::load(&env, &storage, &wallet, &user, &vault, &allowance).next_eligible(&env, &allowance)
+    }
+    pub fn remaining_allowance(env: Env, wallet: Address, user: SignerKey, vault: Address) -> Vec<i128> {
+        let allowance = self::get_vault_allowance(&env, &wallet, &user, &vault);
+        let storage = storage::user_storage(&env, &wallet, &user);
+
+        Standing::load(&env, &storage, &wallet, &user, &vault, &allowance).remaining(&env, &allowance)
+    }
+    // Dry run of policy__ that reports every rule the deposit would fail
+    pub fn check_deposit(env: Env, source: Address, signer: SignerKey, contexts: Vec<Context>) -> Verdict {
+        match self::evaluate(&env, &source, &signer, &contexts) {
+            Ok(_) => Verdict {
+                allowed: true,
+                failures: Vec::new(&env),
+            },
+            Err(failures) => Verdict {
+                allowed: false,
+                failures,
+            },
+        }
+    }
+    // Eligibility of the user in every one of its vaults
+    pub fn get_eligibility(env: Env, wallet: Address, user: SignerKey) -> Vec<Eligibility> {
+        let storage = storage::user_storage(&env, &wallet, &user);
+        let mut eligibility = Vec::new(&env);
+        for (vault, allowance) in self::get_allowances(&env, &wallet, &user).iter() {
+            let standing = Standing::load(&env, &storage, &wallet, &user, &vault, &allowance);
+
+            eligibility.push_back(Eligibility {
+                vault,
+                last_deposit: standing.previous,
+                next_eligible: standing.next_eligible(&env, &allowance),
+                remaining: standing.remaining(&env, &allowance),
+            });
+        }
+
+        eligibility
+    }
+}
+
+// Forgets the deposit history of a user in a vault
+// Every entry of the user in the storage, the storage marker aside
+fn remove_user_state(env: &Env, storage: &SignerStorage, wallet: &Address, user: &SignerKey) {
+    let key = StorageKey::Allowances(wallet.clone(), user.clone());
+
+    if let Some(allowances) = storage::get::<Map<Address, VaultAllowance>>(env, storage, &key) {
+        for vault in allowances.keys().iter() {
+            self::remove_vault_state(env, storage, wallet, user, vault);
+        }
+    }
+
+    storage::remove(env, storage, &key);
+    storage::remove(env, storage, &StorageKey::Frozen(wallet.clone(), user.clone()));
+    storage::remove(env, storage, &StorageKey::Expiration(wallet.clone(), user.clone()));
+}
+
+fn remove_vault_state(env: &Env, storage: &SignerStorage, wallet: &Address, user: &SignerKey, vault: Address) {
+    storage::remove(env, storage, &StorageKey::Previous(wallet.clone(), user.clone(), vault.clone()));
+    storage::remove(env, storage, &StorageKey::Spent(wallet.clone(), user.clone(), vault));
+}
+
+fn to_allowance_map(env: &Env, allowances: Vec<VaultAllowance>) -> Map<Address, VaultAllowance> {
+    if allowances.is_empty() {
+        panic_with_error!(env, Error::InvalidAllowances)
+    }
+
+    let mut allowance_map = Map::new(env);
+    for vault_allowance in allowances.iter() {
+        self::validate_allowance(env, &vault_allowance);
+
+        if allowance_map.contains_key(vault_allowance.vault.clone()) {
+            panic_with_error!(env, Error::InvalidAllowances)
+        }
+        allowance_map.set(vault_allowance.vault.clone(), vault_allowance);
+    }
+
+    allowance_map
+}
+
+fn validate_allowance(env: &Env, vault_allowance: &VaultAllowance) {
+    if vault_allowance.amounts.is_empty() {
+        panic_with_error!(env, Error::InvalidAmount)
+    }
+    for amount in vault_allowance.amounts.iter() {
+        if amount < 0 {
+            panic_with_error!(env, Error::InvalidAmount)
+        }
+    }
+
+    // One cap for each of the assets the vault actually holds
+    let assets = env.invoke_contract::<Vec<AssetStrategySet>>(
+        &vault_allowance.vault,
+        &Symbol::new(env, "get_assets"),
+        Vec::new(env),
+    );
+    if vault_allowance.amounts.len() != assets.len() {
+        panic_with_error!(env, Error::WrongAssetCount)
+    }
+
+    if !vault_allowance.schedule.is_valid() {
+        panic_with_error!(env, Error::InvalidSchedule)
+    }
+
+    if vault_allowance.max_slippage_bps > MAX_BPS {
+        panic_with_error!(env, Error::InvalidSlippage)
+    }
+}
+
+// A deposit that passed every rule of the policy
+struct Approval {
+    user: SignerKey,
+    storage: SignerStorage,
+    vault: Address,
+    allowance: VaultAllowance,
+    standing: Standing,
+    amounts: Vec<i128>,
+}
+
+// Runs every rule of the policy without writing anything, and returns the ones
+// that failed in the order they are checked
+fn evaluate(env: &Env, source: &Address, signer: &SignerKey, contexts: &Vec<Context>) -> Result<Approval, Vec<Error>> {
+    let mut failures = Vec::new(env);
+
+    if self::is_paused(env) {
+        self::fail(&mut failures, Error::Paused);
+    }
+
+    // The remaining rules need the allowance of a single deposit to check against
+    if contexts.len() != 1 {
+        return Err(self::with_failure(failures, Error::NotAllowed));
+    }
+    let user = match signer {
+        SignerKey::Ed25519(_) | SignerKey::Secp256r1(_) => signer.clone(),
+        SignerKey::Policy(_) => return Err(self::with_failure(failures, Error::NotAllowed)),
+    };
+    let (vault, args) = match contexts.get_unchecked(0) {
+        Context::Contract(ContractContext { contract, fn_name, args, .. }) if fn_name == symbol_short!("deposit") => {
+            (contract, args)
+        }
+        _ => return Err(self::with_failure(failures, Error::NotAllowed)),
+    };
+
+    let storage = storage::user_storage(env, source, &user);
+    let allowance = match self::find_allowances(env, &storage, source, &user) {
+        Ok(allowances) => match allowances.get(vault.clone()) {
+            Some(allowance) => allowance,
+            None => return Err(self::with_failure(failures, Error::WrongVault)),
+        },
+        Err(error) => return Err(self::with_failure(failures, error)),
+    };
+
+    if self::is_frozen(env, &storage, source, &user) {
+        self::fail(&mut failures, Error::Frozen);
+    }
+    // In case the wallet still accepts the signer
+    if let Some(expiration) = self::get_expiration(env, &storage, source, &user) {
+        if env.ledger().sequence() > expiration {
+            self::fail(&mut failures, Error::Expired);
+        }
+    }
+
+    let standing = Standing::load(env, &storage, source, &user, &vault, &allowance);
+    if standing.periods == 0 {
+        self::fail(&mut failures, Error::TooSoon);
+    }
+    if standing.is_window_full() {
+        self::fail(&mut failures, Error::TooManyDeposits);
+    }
+    let remaining = standing.remaining(env, &allowance);
+
+    let arg_amounts = self::get_arg::<Vec<i128>>(env, &args, 0);
+    let arg_min_amounts = self::get_arg::<Vec<i128>>(env, &args, 1);
+
+    match (&arg_amounts, &arg_min_amounts) {
+        (Ok(arg_amounts), Ok(arg_min_amounts)) => {
+            // One amount per vault asset, each within its own cap
+            if arg_amounts.len() != allowance.amounts.len() || arg_min_amounts.len() != allowance.amounts.len() {
+                self::fail(&mut failures, Error::WrongAssetCount);
+            } else {
+                for (i, available) in remaining.iter().enumerate() {
+                    let arg_amount = arg_amounts.get_unchecked(i as u32);
+                    let arg_min_amount = arg_min_amounts.get_unchecked(i as u32);
+
+                    if arg_amount < 0 || arg_min_amount < 0 {
+                        self::fail(&mut failures, Error::InvalidAmount);
+                        continue;
+                    }
+                    if arg_amount > available {
+                        self::fail(&mut failures, Error::TooMuch);
+                    }
+                    if !self::within_slippage(arg_amount, arg_min_amount, allowance.max_slippage_bps) {
+                        self::fail(&mut failures, Error::SlippageTooHigh);
+                    }
+                }
+                // Depositing nothing would still record a spend in the window
+                if arg_amounts.iter().all(|arg_amount| arg_amount == 0) {
+                    self::fail(&mut failures, Error::InvalidAmount);
+                }
+            }
+        }
+        (Err(error), _) | (_, Err(error)) => self::fail(&mut failures, *error),
+    }
+
+    // Savings must come out of the smart wallet itself
+    match self::get_arg::<Address>(env, &args, 2) {
+        Ok(from) => {
+            if from != *source {
+                self::fail(&mut failures, Error::WrongFrom);
+            }
+        }
+        Err(error) => self::fail(&mut failures, error),
+    }
+
+    match arg_amounts {
+        Ok(amounts) if failures.is_empty() => Ok(Approval {
+            user,
+            storage,
+            vault,
+            allowance,
+            standing,
+            amounts,
+        }),
+        _ => Err(failures),
+    }
+}
+
+fn fail(failures: &mut Vec<Error>, error: Error) {
+    if !failures.contains(error) {
+        failures.push_back(error);
     }
 }
 
+fn with_failure(mut failures: Vec<Error>, error: Error) -> Vec<Error> {
+    self::fail(&mut failures, error);
+    failures
+}
+
+fn get_arg<T: TryFromVal<Env, Val>>(env: &Env, args: &Vec<Val>, index: u32) -> Result<T, Error> {
+    let val = args.get(index).ok_or(Error::NotAllowed)?;
+
+    T::try_from_val(env, &val).map_err(|_| Error::UnexpectedError)
+}
+
+// Whether min_amount accepts at most max_slippage_bps of slippage on amount
+fn within_slippage(amount: i128, min_amount: i128, max_slippage_bps: u32) -> bool {
+    let min_required = amount.checked_mul(i128::from(MAX_BPS - max_slippage_bps));
+    let min_given = min_amount.checked_mul(i128::from(MAX_BPS));
+
+    match (min_given, min_required) {
+        (Some(min_given), Some(min_required)) => min_given >= min_required,
+        _ => false,
+    }
+}
+
+// Limits the signer to the vaults, and only through this policy
+fn signer_limits(env: &Env, vaults: &Vec<Address>) -> SignerLimits {
+    let mut limits = Map::new(env);
+    for vault in vaults.iter() {
+        limits.set(
+            vault,
+            Some(vec![
+                env,
+                SignerKey::Policy(env.current_contract_address())
+            ]),
+        );
+    }
+
+    SignerLimits(Some(limits))
+}
+
+fn get_allowances(env: &Env, wallet: &Address, user: &SignerKey) -> Map<Address, VaultAllowance> {
+    let storage = storage::user_storage(env, wallet, user);
+
+    self::find_allowances(env, &storage, wallet, user).unwrap_or_else(|error| panic_with_error!(env, error))
+}
+
+// Tells users whose temporary entries lapsed apart from unknown ones
+fn find_allowances(
+    env: &Env,
+    storage: &SignerStorage,
+    wallet: &Address,
+    user: &SignerKey,
+) -> Result<Map<Address, VaultAllowance>, Error> {
+    let key = StorageKey::Allowances(wallet.clone(), user.clone());
+
+    match (storage::get::<Map<Address, VaultAllowance>>(env, storage, &key), storage) {
+        (Some(allowances), _) => Ok(allowances),
+        (None, SignerStorage::Temporary) => Err(Error::Lapsed),
+        (None, SignerStorage::Persistent) => Err(Error::NotFound),
+    }
+}
+
+fn get_vault_allowance(env: &Env, wallet: &Address, user: &SignerKey, vault: &Address) -> VaultAllowance {
+    self::get_allowances(env, wallet, user)
+        .get(vault.clone())
+        .unwrap_or_else(|| panic_with_error!(env, Error::NotFound))
+}
+
 fn get_admin_address(env: &Env) -> Address {
     env.storage()
         .instance()
@@ -122,94 +788,51 @@ fn get_admin_address(env: &Env) -> Address {
         .unwrap_or_else(|| panic_with_error!(&env, Error::NotInitialized))
 }
 
-#[contractimpl]
-impl PolicyInterface for Contract {
-    fn policy__(env: Env, _source: Address, signer: SignerKey, contexts: Vec<Context>) {
-        // if contexts.len() == 1 {
-        //     if let SignerKey::Ed25519(user) = signer {
-        //         if let Context::Contract(ContractContext { fn_name, args, .. }) = contexts.get_unchecked(0) {
-        //             if fn_name == symbol_short!("transfer") {
-        //                 if let Some(amount_val) = args.get(2) {
-        //                     if let Ok(arg_amount) = i128::try_from_val(&env, &amount_val) {
-        //                         let (interval, amount) = env
-        //                             .storage()
-        //                             .persistent()
-        //                             .get::<BytesN<32>, (u32, i128)>(&user.clone())
-        //                             .unwrap_or_else(|| {
-        //                                 panic_with_error!(&env, Error::NotFound)
-        //                             });
-
-        //                         let current = env.ledger().sequence();
-        //                         let previous = env
-        //                             .storage()
-        //                             .persistent()
-        //                             .get::<StorageKey, u32>(&StorageKey::Previous(user.clone()))
-        //                             .unwrap_or(env.ledger().sequence() - interval);
-
-        //                         let x = (current - previous) / interval;
-
-        //                         if x <= 0 {
-        //                             panic_with_error!(&env, Error::TooSoon)
-        //                         }
-
-        //                         if arg_amount > (amount * i128::from(x)) {
-        //                             panic_with_error!(&env, Error::TooMuch)
-        //                         }
-
-        //                         env.storage()
-        //                             .persistent()
-        //                             .set(&StorageKey::Previous(user.clone()), &current);
-
-        //                         return;
-        //                     }
-        //                 }
-        //             }
-        //         }
-        //     }
-        // }
-
-        if contexts.len() == 1 {
-            if let SignerKey::Ed25519(user) = signer {
-            if let Context::Contract(ContractContext { contract, fn_name, args, .. }) = contexts.get_unchecked(0) {
-                if fn_name == symbol_short!("deposit") {
-                    let previous = env
-                        .storage()
-                        .persistent()
-                        .get::<StorageKey, u32>(&StorageKey::Previous(user.clone()))
-                        .unwrap_or(env.ledger().sequence() - MONTH_IN_LEDGERS);
-                    if env.ledger().sequence() - previous < MONTH_IN_LEDGERS {
-                        panic_with_error!(&env, Error::TooSoon);
-                    }
+fn is_paused(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get::<StorageKey, bool>(&StorageKey::Paused)
+        .unwrap_or(false)
+}
 
-                    let allowed_amount = env.storage()
-                    .persistent()
-                    .get::<StorageKey, VaultAllowance>(&StorageKey::VaultAllowance(user.clone()))
-                    .unwrap_or_else(|| panic_with_error!(&env, Error::NotFound));
-                    if let Some(amount_val) = args.get(0) {
-                        if let Ok(arg_amount) = Vec::<i128>::try_from_val(&env, &amount_val) {
-                            if arg_amount.get(0).unwrap() > allowed_amount.amount {
-                                panic_with_error!(&env, Error::TooMuch);
-                            }
-                        } else {
-                            panic_with_error!(&env, Error::UnexpectedError);
-                        }
-                    } else {
-                        panic_with_error!(&env, Error::NotAllowed);
-                    }
+fn is_frozen(env: &Env, storage: &SignerStorage, wallet: &Address, user: &SignerKey) -> bool {
+    storage::get::<bool>(env, storage, &StorageKey::Frozen(wallet.clone(), user.clone())).unwrap_or(false)
+}
 
-                    if contract != allowed_amount.vault {
-                        panic_with_error!(&env, Error::WrongVault);
-                    }
+fn get_expiration(env: &Env, storage: &SignerStorage, wallet: &Address, user: &SignerKey) -> Option<u32> {
+    storage::get::<u32>(env, storage, &StorageKey::Expiration(wallet.clone(), user.clone()))
+}
 
-                    env.storage()
-                    .persistent()
-                    .set::<StorageKey, u32>(&StorageKey::Previous(user.clone()), &env.ledger().sequence());
-                    return;
-                    }
-                }
-            }
+fn get_pending_admin_address(env: &Env) -> Address {
+    env.storage()
+        .instance()
+        .get::<StorageKey, Address>(&StorageKey::PendingAdmin)
+        .unwrap_or_else(|| panic_with_error!(env, Error::NoPendingAdmin))
+}
+
+#[contractimpl]
+impl PolicyInterface for Contract {
+    fn policy__(env: Env, source: Address, signer: SignerKey, contexts: Vec<Context>) {
+        // The wallet invokes the policy itself, anyone else could burn its allowance
+        source.require_auth();
+
+        let Approval { user, storage, vault, allowance, mut standing, amounts } =
+            self::evaluate(&env, &source, &signer, &contexts)
+                .unwrap_or_else(|failures| panic_with_error!(&env, failures.get_unchecked(0)));
+
+        if let Schedule::Rolling(_) = allowance.schedule {
+            standing.spends.push_back(Spend {
+                timestamp: standing.now,
+                amounts: amounts.clone(),
+            });
+            storage::set::<Vec<Spend>>(&env, &storage, &StorageKey::Spent(source.clone(), user.clone(), vault.clone()), &standing.spends);
         }
 
-        panic_with_error!(&env, Error::NotAllowed)
+        storage::set::<u64>(&env, &storage, &StorageKey::Previous(source.clone(), user.clone(), vault.clone()), &standing.now);
+
+        ttl::extend_instance(&env);
+        ttl::extend_user_vault(&env, &source, &user, &vault);
+
+        events::deposit(&env, source, user, vault, amounts);
     }
 }
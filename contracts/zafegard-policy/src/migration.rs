@@ -0,0 +1,77 @@
+use smart_wallet_interface::types::SignerKey;
+use soroban_sdk::{contracttype, vec, Address, BytesN, Env, Map};
+
+use crate::{Schedule, StorageKey, VaultAllowance, MAX_BPS};
+
+// Version of the storage layout this code reads and writes
+pub const STORAGE_VERSION: u32 = 1;
+
+// Version 0 assumed 5 second ledgers and a single 30 day interval for everyone
+const V0_LEDGER_SECONDS: u64 = 5;
+const V0_INTERVAL: u64 = 3600 * 24 * 30;
+
+// Keys written by version 0, which kept a single vault per user
+#[contracttype(export = false)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum StorageKeyV0 {
+    Previous(BytesN<32>),
+    VaultAllowance(BytesN<32>),
+}
+
+#[contracttype(export = false)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct VaultAllowanceV0 {
+    pub vault: Address,
+    pub amount: i128,
+}
+
+pub fn storage_version(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get::<StorageKey, u32>(&StorageKey::Version)
+        .unwrap_or(0)
+}
+
+// Does nothing without the old keys, so it runs regardless of the storage version
+// and users left behind can still be migrated after finish_migration. Version 0
+// served a single wallet and only had Ed25519 users, keyed by their public key
+pub fn migrate_user(env: &Env, wallet: &Address, user: &BytesN<32>) {
+    let key = StorageKeyV0::VaultAllowance(user.clone());
+    let Some(old_allowance) = env
+        .storage()
+        .persistent()
+        .get::<StorageKeyV0, VaultAllowanceV0>(&key)
+    else {
+        return;
+    };
+
+    let signer = SignerKey::Ed25519(user.clone());
+
+    // Deposits are now spaced by timestamps instead of ledgers
+    let previous_key = StorageKeyV0::Previous(user.clone());
+    if let Some(previous) = env.storage().persistent().get::<StorageKeyV0, u32>(&previous_key) {
+        let elapsed = u64::from(env.ledger().sequence().saturating_sub(previous)) * V0_LEDGER_SECONDS;
+
+        env.storage().persistent().set(
+            &StorageKey::Previous(wallet.clone(), signer.clone(), old_allowance.vault.clone()),
+            &env.ledger().timestamp().saturating_sub(elapsed),
+        );
+        env.storage().persistent().remove(&previous_key);
+    }
+
+    // Version 0 never looked at min_amounts, so slippage stays unrestricted
+    let allowance = VaultAllowance {
+        vault: old_allowance.vault.clone(),
+        amounts: vec![env, old_allowance.amount],
+        schedule: Schedule::Interval(V0_INTERVAL),
+        max_slippage_bps: MAX_BPS,
+        max_carry_over: 0,
+    };
+    let mut allowances = Map::new(env);
+    allowances.set(old_allowance.vault, allowance);
+
+    env.storage()
+        .persistent()
+        .set::<StorageKey, Map<Address, VaultAllowance>>(&StorageKey::Allowances(wallet.clone(), signer), &allowances);
+    env.storage().persistent().remove(&key);
+}
@@ -0,0 +1,79 @@
+use crate::Schedule;
+
+const SECONDS_PER_DAY: u64 = 24 * 3600;
+
+impl Schedule {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Schedule::Interval(interval) => *interval > 0,
+            // Every month has at least 28 days, so the anchor exists in all of them
+            Schedule::Monthly(day) => (1..=28).contains(day),
+            Schedule::Rolling(window) => *window > 0,
+        }
+    }
+
+    // Number of whole periods between a deposit made at `previous` and `now`
+    pub fn periods_between(&self, previous: u64, now: u64) -> u64 {
+        match self {
+            Schedule::Interval(interval) => (now - previous) / interval,
+            Schedule::Monthly(day) => month_period(now, *day) - month_period(previous, *day),
+            // A rolling budget is always open, what was spent in the window limits it instead
+            Schedule::Rolling(_) => 1,
+        }
+    }
+
+    // Timestamp from which the deposit after the one made at `previous` is allowed
+    pub fn next_due(&self, previous: u64) -> u64 {
+        match self {
+            Schedule::Interval(interval) => previous.saturating_add(*interval),
+            Schedule::Monthly(day) => month_period_start(month_period(previous, *day) + 1, *day),
+            Schedule::Rolling(_) => previous,
+        }
+    }
+}
+
+// Converts a month_period back into the timestamp the period starts at
+fn month_period_start(period: u64, day: u32) -> u64 {
+    days_from_civil(period / 12, period % 12 + 1, u64::from(day)) * SECONDS_PER_DAY
+}
+
+// Number of months since year 0 of the period `timestamp` falls in, where a
+// period starts at 00:00 UTC on `day` of each month
+fn month_period(timestamp: u64, day: u32) -> u64 {
+    let (year, month, month_day) = civil_from_days(timestamp / SECONDS_PER_DAY);
+    let period = year * 12 + (month - 1);
+
+    if month_day < u64::from(day) {
+        period - 1
+    } else {
+        period
+    }
+}
+
+// Converts days since 1970-01-01 into a (year, month, day) UTC date
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+// Converts a (year, month, day) UTC date into days since 1970-01-01
+// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146_097 + doe - 719_468
+}
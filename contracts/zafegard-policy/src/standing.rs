@@ -0,0 +1,114 @@
+use smart_wallet_interface::types::{SignerKey, SignerStorage};
+use soroban_sdk::{Address, Env, Vec};
+
+use crate::{storage, Schedule, Spend, StorageKey, VaultAllowance, MAX_WINDOW_DEPOSITS};
+
+// Where a user's allowance in a vault stands at the current ledger
+pub struct Standing {
+    pub now: u64,
+    pub previous: Option<u64>,
+    // Number of periods whose amounts can be deposited now, 0 when it's too soon
+    pub periods: u64,
+    // Deposits still inside the rolling window
+    pub spends: Vec<Spend>,
+}
+
+impl Standing {
+    pub fn load(
+        env: &Env,
+        storage: &SignerStorage,
+        wallet: &Address,
+        user: &SignerKey,
+        vault: &Address,
+        allowance: &VaultAllowance,
+    ) -> Self {
+        let now = env.ledger().timestamp();
+        let previous = storage::get::<u64>(env, storage, &StorageKey::Previous(wallet.clone(), user.clone(), vault.clone()));
+
+        // Periods missed since the last deposit carry over, up to max_carry_over
+        let periods = match previous {
+            Some(previous) => allowance
+                .schedule
+                .periods_between(previous, now)
+                .min(1 + u64::from(allowance.max_carry_over)),
+            None => 1,
+        };
+
+        let spends = match allowance.schedule {
+            Schedule::Rolling(window) => {
+                window_spends(env, storage, &StorageKey::Spent(wallet.clone(), user.clone(), vault.clone()), window, now)
+            }
+            _ => Vec::new(env),
+        };
+
+        Standing {
+            now,
+            previous,
+            periods,
+            spends,
+        }
+    }
+
+    pub fn is_window_full(&self) -> bool {
+        self.spends.len() >= MAX_WINDOW_DEPOSITS
+    }
+
+    // What can still be deposited right now for each of the vault assets
+    pub fn remaining(&self, env: &Env, allowance: &VaultAllowance) -> Vec<i128> {
+        let mut remaining = Vec::new(env);
+        for (i, cap) in allowance.amounts.iter().enumerate() {
+            let spent: i128 = self
+                .spends
+                .iter()
+                .map(|spend| spend.amounts.get(i as u32).unwrap_or(0))
+                .sum();
+
+            remaining.push_back(
+                cap.saturating_mul(i128::from(self.periods))
+                    .saturating_sub(spent)
+                    .max(0),
+            );
+        }
+
+        remaining
+    }
+
+    // Earliest timestamp at which a deposit can be made
+    pub fn next_eligible(&self, env: &Env, allowance: &VaultAllowance) -> u64 {
+        match (&allowance.schedule, self.previous) {
+            (_, None) => self.now,
+            (Schedule::Rolling(window), Some(_)) => {
+                if !self.is_window_full() && self.remaining(env, allowance).iter().any(|x| x > 0) {
+                    self.now
+                } else {
+                    // Budget frees up as soon as the oldest deposit leaves the window
+                    self.spends
+                        .first()
+                        .map(|spend| spend.timestamp.saturating_add(*window))
+                        .unwrap_or(self.now)
+                }
+            }
+            (schedule, Some(previous)) => {
+                if self.periods > 0 {
+                    self.now
+                } else {
+                    schedule.next_due(previous)
+                }
+            }
+        }
+    }
+}
+
+// Deposits made less than `window` seconds before `now`
+fn window_spends(env: &Env, storage: &SignerStorage, key: &StorageKey, window: u64, now: u64) -> Vec<Spend> {
+    let mut spends = Vec::new(env);
+    if let Some(stored) = storage::get::<Vec<Spend>>(env, storage, key) {
+        for spend in stored.iter() {
+            if now - spend.timestamp < window {
+                spends.push_back(spend);
+            }
+        }
+    }
+
+    spends
+}
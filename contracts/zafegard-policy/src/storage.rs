@@ -0,0 +1,41 @@
+use smart_wallet_interface::types::{SignerKey, SignerStorage};
+use soroban_sdk::{Address, Env, IntoVal, TryFromVal, Val};
+
+use crate::StorageKey;
+
+// The entries of a user live in the same storage as its signer. Only temporary
+// users are marked, so that their lapsed entries can be told from unknown users
+pub fn user_storage(env: &Env, wallet: &Address, user: &SignerKey) -> SignerStorage {
+    env.storage()
+        .persistent()
+        .get::<StorageKey, SignerStorage>(&StorageKey::Storage(wallet.clone(), user.clone()))
+        .unwrap_or(SignerStorage::Persistent)
+}
+
+pub fn get<V: TryFromVal<Env, Val>>(env: &Env, storage: &SignerStorage, key: &StorageKey) -> Option<V> {
+    match storage {
+        SignerStorage::Persistent => env.storage().persistent().get::<StorageKey, V>(key),
+        SignerStorage::Temporary => env.storage().temporary().get::<StorageKey, V>(key),
+    }
+}
+
+pub fn set<V: IntoVal<Env, Val>>(env: &Env, storage: &SignerStorage, key: &StorageKey, value: &V) {
+    match storage {
+        SignerStorage::Persistent => env.storage().persistent().set::<StorageKey, V>(key, value),
+        SignerStorage::Temporary => env.storage().temporary().set::<StorageKey, V>(key, value),
+    }
+}
+
+pub fn has(env: &Env, storage: &SignerStorage, key: &StorageKey) -> bool {
+    match storage {
+        SignerStorage::Persistent => env.storage().persistent().has(key),
+        SignerStorage::Temporary => env.storage().temporary().has(key),
+    }
+}
+
+pub fn remove(env: &Env, storage: &SignerStorage, key: &StorageKey) {
+    match storage {
+        SignerStorage::Persistent => env.storage().persistent().remove(key),
+        SignerStorage::Temporary => env.storage().temporary().remove(key),
+    }
+}
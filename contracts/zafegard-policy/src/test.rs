@@ -4,14 +4,18 @@ use std::println;
 extern crate std;
 
 use smart_wallet::{Contract as SmartWalletContract};
-use smart_wallet_interface::types::{Signer, SignerExpiration, SignerKey, SignerLimits, SignerStorage};
+use smart_wallet_interface::{
+    types::{Signer, SignerExpiration, SignerKey, SignerLimits, SignerStorage},
+    SmartWalletClient,
+};
 use soroban_sdk::{
     auth::{Context, ContractContext}, 
     symbol_short, 
-    testutils::{Address as _, BytesN as _, EnvTestConfig, Ledger as _}, 
+    testutils::{storage::{Persistent as _, Temporary as _}, Address as _, BytesN as _, EnvTestConfig, Events as _, Ledger as _}, 
     vec, 
     xdr::ToXdr, 
     Address, 
+    Bytes, 
     BytesN, 
     Env, 
     Error as SorobanError, 
@@ -23,12 +27,21 @@ use soroban_sdk::{
     token::{StellarAssetClient as SorobanTokenAdminClient, TokenClient as SorobanTokenClient},
 };
 
-use crate::{Contract, ContractClient, Error};
+use crate::{
+    events::{AdminChangedEvent, AdminTransferEvent, DepositEvent, UserEvent},
+    migration::{StorageKeyV0, VaultAllowanceV0},
+    types::SaverSigner,
+    Contract, ContractClient, Eligibility, Error, Schedule, StorageKey, TtlConfig, VaultAllowance, Verdict,
+};
 
 // Import AssetStrategySet from common
 use common::models::AssetStrategySet;
 
-const MONTH_IN_LEDGERS: u32 = 3600*24*30/5;
+const MONTH_IN_SECONDS: u64 = 3600*24*30;
+const WEEK_IN_SECONDS: u64 = 3600*24*7;
+const DAY_IN_SECONDS: u64 = 3600*24;
+// 2025-01-01T00:00:00Z
+const JANUARY_2025: u64 = 1735689600;
 
 fn vault_contract_wasm(e: &Env) -> BytesN<32> {
     soroban_sdk::contractimport!(file = "../defindex/defindex_vault.optimized.wasm");
@@ -129,6 +142,39 @@ pub fn create_test_vault<'a>(e: &Env) -> (VaultClient<'a>, SorobanTokenClient<'a
     (vault, token, emergency_manager, vault_fee_receiver, manager, rebalance_manager)
 }
 
+// Create a vault holding `count` assets, without any deposit
+pub fn create_multi_asset_vault<'a>(e: &Env, count: u32) -> VaultClient<'a> {
+    let mut assets = Vec::new(e);
+    for _ in 0..count {
+        let token = create_token_contract(e, &Address::generate(e));
+        assets.push_back(AssetStrategySet {
+            address: token.address.clone(),
+            strategies: Vec::new(e),
+        });
+    }
+
+    let mut roles = Map::new(e);
+    for role in 0..4 {
+        roles.set(role, Address::generate(e));
+    }
+
+    let mut name_symbol = Map::new(e);
+    name_symbol.set(String::from_str(e, "name"), String::from_str(e, "Test Vault"));
+    name_symbol.set(String::from_str(e, "symbol"), String::from_str(e, "TV"));
+
+    create_vault(
+        e,
+        assets,
+        roles,
+        0,
+        Address::generate(e),
+        0,
+        Address::generate(e),
+        name_symbol,
+        true,
+    )
+}
+
 
 // Create Test Token
 pub(crate) fn create_token_contract<'a>(e: &Env, admin: &Address) -> SorobanTokenClient<'a> {
@@ -139,31 +185,38 @@ pub(crate) fn create_token_contract<'a>(e: &Env, admin: &Address) -> SorobanToke
     )
 }
 
-#[test]
-fn save_money_success() {
-    let mut env = Env::default();
-    env.set_config(EnvTestConfig {
-        capture_snapshot_at_drop: false,
-    });
-
+// Registers a wallet with its own policy at the usual ledger of the tests, along
+// with a session key that isn't added to the policy yet
+fn setup<'a>(env: &Env) -> (Address, ContractClient<'a>, BytesN<32>) {
     env.ledger().set_min_persistent_entry_ttl(u32::MAX / 2);
     env.mock_all_auths();
-    env.ledger().set_sequence_number(2*MONTH_IN_LEDGERS);
+    env.ledger().set_timestamp(2*MONTH_IN_SECONDS);
 
-    let (vault, token, emergency_manager, vault_fee_receiver, manager, rebalance_manager) = create_test_vault(&env);
-    
-    let root_signer = Signer::Ed25519(BytesN::<32>::random(&env), SignerExpiration(None), SignerLimits(None), SignerStorage::Temporary);
-    let wallet = env.register(SmartWalletContract, (root_signer, ));
+    let wallet = create_wallet(env);
+    let automated_savings_client = ContractClient::new(env, &env.register(Contract, (wallet.clone(), )));
+
+    (wallet, automated_savings_client, address_to_bytes(env, &Address::generate(env)))
+}
+
+fn create_wallet(env: &Env) -> Address {
+    let root_signer = Signer::Ed25519(BytesN::<32>::random(env), SignerExpiration(None), SignerLimits(None), SignerStorage::Temporary);
+    env.register(SmartWalletContract, (root_signer, ))
+}
 
-    let automated_savings_address = env.register(Contract, ());
-    let automated_savings_client = ContractClient::new(&env, &automated_savings_address);
+// Adds the session key as a persistent saver that never expires
+fn add_saver(client: &ContractClient, wallet: &Address, user_bytes: &BytesN<32>, allowances: Vec<VaultAllowance>) {
+    client.add_wallet(wallet, &SaverSigner::Ed25519(user_bytes.clone()), &allowances, &None, &SignerStorage::Persistent);
+}
+
+#[test]
+fn save_money_success() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
 
-    let user = Address::generate(&env);
-    let user_bytes = address_to_bytes(&env, &user);
+    let (vault, token, emergency_manager, vault_fee_receiver, manager, rebalance_manager) = create_test_vault(&env);
     let amount = 100;
 
-    automated_savings_client.init(&wallet);
-    automated_savings_client.add_wallet(&user_bytes, &vault.address, &amount);
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
 
     // Success deposit
     let contexts = vec![
@@ -175,19 +228,27 @@ fn save_money_success() {
                 &env,
                 vec![&env, amount].try_into_val(&env).unwrap(), // amounts
                 vec![&env, amount].try_into_val(&env).unwrap(), // min_amounts
-                user.to_val(),      // from
+                wallet.to_val(),      // from
                 false.into_val(&env),     // claim
             ],
         }),
     ];
 
+    // Test that the deposit must come from the wallet
+    let failed_deposit = automated_savings_client.try_policy__(
+        &wallet,
+        &SignerKey::Ed25519(user_bytes.clone()),
+        &deposit_contexts(&env, &vault.address, &Address::generate(&env), vec![&env, amount]),
+    );
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::WrongFrom))));
+
     automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
 
     // Test that can't deposit before the month
     let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
     assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
 
-    env.ledger().set_sequence_number(3*MONTH_IN_LEDGERS);
+    env.ledger().set_timestamp(3*MONTH_IN_SECONDS);
 
     // Test that only can be deposited the amount allowed
     let contexts = vec![
@@ -199,7 +260,7 @@ fn save_money_success() {
                 &env,
                 vec![&env, amount+1].try_into_val(&env).unwrap(), // amounts
                 vec![&env, amount+1].try_into_val(&env).unwrap(), // min_amounts
-                user.to_val(),      // from
+                wallet.to_val(),      // from
             ],
         }),
     ];
@@ -217,7 +278,7 @@ fn save_money_success() {
                 &env,
                 vec![&env, amount].try_into_val(&env).unwrap(), // amounts
                 vec![&env, amount].try_into_val(&env).unwrap(), // min_amounts
-                user.to_val(),      // from
+                wallet.to_val(),      // from
             ],
         }),
     ];
@@ -235,7 +296,7 @@ fn save_money_success() {
                 &env,
                 vec![&env, amount].try_into_val(&env).unwrap(), // amounts
                 vec![&env, amount].try_into_val(&env).unwrap(), // min_amounts
-                user.to_val(),      // from
+                wallet.to_val(),      // from
             ],
         }),
     ];
@@ -244,6 +305,1081 @@ fn save_money_success() {
     assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::NotAllowed))));
 }
 
+#[test]
+fn custom_interval_is_enforced() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    // An interval of zero would allow unlimited deposits
+    let failed_add = automated_savings_client.try_add_wallet(
+        &wallet,
+        &SaverSigner::Ed25519(user_bytes.clone()),
+        &vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(0))],
+        &None,
+        &SignerStorage::Persistent,
+    );
+    assert_eq!(failed_add, Err(Ok(SorobanError::from(Error::InvalidSchedule))));
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(WEEK_IN_SECONDS))]);
+
+    let contexts = deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    env.ledger().set_timestamp(2*MONTH_IN_SECONDS + WEEK_IN_SECONDS - 1);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
+
+    // Ledger sequence doesn't matter, only the close time does
+    env.ledger().set_sequence_number(env.ledger().sequence() + WEEK_IN_SECONDS as u32);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
+
+    // A weekly saver doesn't have to wait for a whole month
+    env.ledger().set_timestamp(2*MONTH_IN_SECONDS + WEEK_IN_SECONDS);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    // Any interval that passes validation can be queried
+    automated_savings_client.update_wallet(&wallet, &SaverSigner::Ed25519(user_bytes.clone()), &vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(u64::MAX))]);
+    assert_eq!(automated_savings_client.next_eligible(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &vault.address), u64::MAX);
+}
+
+#[test]
+fn monthly_schedule_keeps_its_anchor() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    // 2025-01-15
+    env.ledger().set_timestamp(JANUARY_2025 + 14*DAY_IN_SECONDS);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    let failed_add = automated_savings_client.try_add_wallet(
+        &wallet,
+        &SaverSigner::Ed25519(user_bytes.clone()),
+        &vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Monthly(29))],
+        &None,
+        &SignerStorage::Persistent,
+    );
+    assert_eq!(failed_add, Err(Ok(SorobanError::from(Error::InvalidSchedule))));
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Monthly(1))]);
+
+    let contexts = deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    // 2025-01-31, still January
+    env.ledger().set_timestamp(JANUARY_2025 + 30*DAY_IN_SECONDS);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
+
+    // 2025-02-01, a new month only half a month after the last deposit
+    env.ledger().set_timestamp(JANUARY_2025 + 31*DAY_IN_SECONDS);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    // 2025-04-20, March was skipped
+    env.ledger().set_timestamp(JANUARY_2025 + 109*DAY_IN_SECONDS);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    // 2025-04-30
+    env.ledger().set_timestamp(JANUARY_2025 + 119*DAY_IN_SECONDS);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
+
+    // 2025-05-01, the late April deposit didn't move the anchor
+    env.ledger().set_timestamp(JANUARY_2025 + 120*DAY_IN_SECONDS);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+}
+
+#[test]
+fn update_wallet_persists_changes() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let (new_vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    let failed_update = automated_savings_client.try_update_wallet(
+        &wallet,
+        &SaverSigner::Ed25519(user_bytes.clone()),
+        &vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))],
+    );
+    assert_eq!(failed_update, Err(Ok(SorobanError::from(Error::NotFound))));
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+
+    let failed_update = automated_savings_client.try_update_wallet(
+        &wallet,
+        &SaverSigner::Ed25519(user_bytes.clone()),
+        &vec![&env, vault_allowance(&vault.address, vec![&env, -1], Schedule::Interval(MONTH_IN_SECONDS))],
+    );
+    assert_eq!(failed_update, Err(Ok(SorobanError::from(Error::InvalidAmount))));
+
+    let failed_update = automated_savings_client.try_update_wallet(&wallet, &SaverSigner::Ed25519(user_bytes.clone()), &vec![&env]);
+    assert_eq!(failed_update, Err(Ok(SorobanError::from(Error::InvalidAllowances))));
+
+    automated_savings_client.update_wallet(
+        &wallet,
+        &SaverSigner::Ed25519(user_bytes.clone()),
+        &vec![&env, vault_allowance(&new_vault.address, vec![&env, 2*amount], Schedule::Interval(MONTH_IN_SECONDS))],
+    );
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::WrongVault))));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &new_vault.address, &wallet, vec![&env, 2*amount]));
+}
+
+#[test]
+fn remove_wallet_cleans_up() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    let failed_remove = automated_savings_client.try_remove_wallet(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert_eq!(failed_remove, Err(Ok(SorobanError::from(Error::NotFound))));
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+
+    let contexts = deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    automated_savings_client.remove_wallet(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::NotFound))));
+
+    // Re-adding the user starts from a clean slate
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+}
+
+#[test]
+fn multi_asset_amounts_are_capped() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let vault = create_multi_asset_vault(&env, 2).address;
+
+    // One cap for each of the vault assets
+    let failed_add = automated_savings_client.try_add_wallet(
+        &wallet,
+        &SaverSigner::Ed25519(user_bytes.clone()),
+        &vec![&env, vault_allowance(&vault, vec![&env, 100], Schedule::Interval(MONTH_IN_SECONDS))],
+        &None,
+        &SignerStorage::Persistent,
+    );
+    assert_eq!(failed_add, Err(Ok(SorobanError::from(Error::WrongAssetCount))));
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault, vec![&env, 100, 50], Schedule::Interval(MONTH_IN_SECONDS))]);
+
+    // The second asset is capped too
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault, &wallet, vec![&env, 100, 51]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooMuch))));
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault, &wallet, vec![&env, 100]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::WrongAssetCount))));
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault, &wallet, vec![&env, 100, 50, 1]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::WrongAssetCount))));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault, &wallet, vec![&env, 100, 50]));
+}
+
+#[test]
+fn several_vaults_per_user() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (stable_vault, _, _, _, _, _) = create_test_vault(&env);
+    let (xlm_vault, _, _, _, _, _) = create_test_vault(&env);
+
+    // A vault can only be listed once
+    let failed_add = automated_savings_client.try_add_wallet(&wallet, &SaverSigner::Ed25519(user_bytes.clone()), &vec![
+        &env,
+        vault_allowance(&stable_vault.address, vec![&env, 100], Schedule::Interval(MONTH_IN_SECONDS)),
+        vault_allowance(&stable_vault.address, vec![&env, 50], Schedule::Interval(WEEK_IN_SECONDS)),
+    ], &None, &SignerStorage::Persistent);
+    assert_eq!(failed_add, Err(Ok(SorobanError::from(Error::InvalidAllowances))));
+
+    automated_savings_client.add_wallet(&wallet, &SaverSigner::Ed25519(user_bytes.clone()), &vec![
+        &env,
+        vault_allowance(&stable_vault.address, vec![&env, 100], Schedule::Interval(MONTH_IN_SECONDS)),
+        vault_allowance(&xlm_vault.address, vec![&env, 50], Schedule::Interval(WEEK_IN_SECONDS)),
+    ], &None, &SignerStorage::Persistent);
+
+    let stable_contexts = deposit_contexts(&env, &stable_vault.address, &wallet, vec![&env, 100]);
+    let xlm_contexts = deposit_contexts(&env, &xlm_vault.address, &wallet, vec![&env, 50]);
+
+    // Each vault has its own cooldown
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &stable_contexts);
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &xlm_contexts);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &xlm_contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
+
+    env.ledger().set_timestamp(2*MONTH_IN_SECONDS + WEEK_IN_SECONDS);
+
+    // And its own cap
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &xlm_vault.address, &wallet, vec![&env, 100]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooMuch))));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &xlm_contexts);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &stable_contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
+}
+
+#[test]
+fn min_amounts_respect_max_slippage() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+
+    let mut allowance = vault_allowance(&vault.address, vec![&env, 1000], Schedule::Interval(MONTH_IN_SECONDS));
+    allowance.max_slippage_bps = 10_001;
+
+    let failed_add = automated_savings_client.try_add_wallet(&wallet, &SaverSigner::Ed25519(user_bytes.clone()), &vec![&env, allowance.clone()], &None, &SignerStorage::Persistent);
+    assert_eq!(failed_add, Err(Ok(SorobanError::from(Error::InvalidSlippage))));
+
+    // 1%
+    allowance.max_slippage_bps = 100;
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, allowance]);
+
+    let failed_deposit = automated_savings_client.try_policy__(
+        &wallet,
+        &SignerKey::Ed25519(user_bytes.clone()),
+        &deposit_contexts_with_min(&env, &vault.address, &wallet, vec![&env, 1000], vec![&env, 0]),
+    );
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::SlippageTooHigh))));
+
+    let failed_deposit = automated_savings_client.try_policy__(
+        &wallet,
+        &SignerKey::Ed25519(user_bytes.clone()),
+        &deposit_contexts_with_min(&env, &vault.address, &wallet, vec![&env, 1000], vec![&env, 989]),
+    );
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::SlippageTooHigh))));
+
+    let failed_deposit = automated_savings_client.try_policy__(
+        &wallet,
+        &SignerKey::Ed25519(user_bytes.clone()),
+        &deposit_contexts_with_min(&env, &vault.address, &wallet, vec![&env, 1000], vec![&env, 990, 0]),
+    );
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::WrongAssetCount))));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts_with_min(&env, &vault.address, &wallet, vec![&env, 1000], vec![&env, 990]));
+}
+
+#[test]
+fn missed_periods_carry_over() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    let mut allowance = vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS));
+    allowance.max_carry_over = 1;
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, allowance]);
+
+    // Nothing accrues before the first deposit
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, 2*amount]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooMuch))));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]));
+
+    // Two months were missed but only one carries over
+    env.ledger().set_timestamp(5*MONTH_IN_SECONDS);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, 3*amount]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooMuch))));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, 2*amount]));
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
+}
+
+#[test]
+fn rolling_window_budget() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Rolling(MONTH_IN_SECONDS))]);
+
+    // Empty deposits don't take a slot of the window
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, 0]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::InvalidAmount))));
+
+    let contexts = deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount / 4]);
+
+    // Four weekly deposits use up the monthly budget
+    for week in 0..4 {
+        env.ledger().set_timestamp(2*MONTH_IN_SECONDS + week*WEEK_IN_SECONDS);
+        automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    }
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, 1]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooMuch))));
+
+    // Once the first deposit leaves the window its amount is available again
+    env.ledger().set_timestamp(3*MONTH_IN_SECONDS);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount / 4 + 1]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooMuch))));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    // Any window that passes validation can be queried
+    automated_savings_client.update_wallet(&wallet, &SaverSigner::Ed25519(user_bytes.clone()), &vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Rolling(u64::MAX))]);
+    assert_eq!(automated_savings_client.next_eligible(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &vault.address), u64::MAX);
+}
+
+#[test]
+fn events_are_published() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]));
+
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        vec![
+            &env,
+            (
+                automated_savings_client.address.clone(),
+                (symbol_short!("zafegard"), symbol_short!("deposit"), wallet.clone()).into_val(&env),
+                DepositEvent {
+                    wallet: wallet.clone(),
+                    user: SignerKey::Ed25519(user_bytes.clone()),
+                    vault: vault.address.clone(),
+                    amounts: vec![&env, amount],
+                    ledger: env.ledger().sequence(),
+                    timestamp: env.ledger().timestamp(),
+                }.into_val(&env),
+            ),
+        ]
+    );
+
+    automated_savings_client.remove_wallet(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        vec![
+            &env,
+            (
+                automated_savings_client.address.clone(),
+                (symbol_short!("zafegard"), symbol_short!("removed"), wallet.clone()).into_val(&env),
+                UserEvent {
+                    wallet: wallet.clone(),
+                    user: SignerKey::Ed25519(user_bytes.clone()),
+                }.into_val(&env),
+            ),
+        ]
+    );
+}
+
+#[test]
+fn queries_follow_the_policy() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    // 2025-01-15
+    env.ledger().set_timestamp(JANUARY_2025 + 14*DAY_IN_SECONDS);
+
+    let (stable_vault, _, _, _, _, _) = create_test_vault(&env);
+    let (xlm_vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    let failed_query = automated_savings_client.try_get_allowance(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert_eq!(failed_query, Err(Ok(SorobanError::from(Error::NotFound))));
+
+    let stable_allowance = vault_allowance(&stable_vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS));
+    let xlm_allowance = vault_allowance(&xlm_vault.address, vec![&env, amount], Schedule::Monthly(1));
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, stable_allowance.clone(), xlm_allowance.clone()]);
+
+    let allowances = automated_savings_client.get_allowance(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert_eq!(allowances.len(), 2);
+    assert!(allowances.contains(&stable_allowance));
+    assert!(allowances.contains(&xlm_allowance));
+
+    let now = env.ledger().timestamp();
+    assert_eq!(automated_savings_client.get_last_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &stable_vault.address), None);
+    assert_eq!(automated_savings_client.next_eligible(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &stable_vault.address), now);
+    assert_eq!(automated_savings_client.remaining_allowance(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &stable_vault.address), vec![&env, amount]);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &stable_vault.address, &wallet, vec![&env, amount]));
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &xlm_vault.address, &wallet, vec![&env, amount]));
+
+    assert_eq!(automated_savings_client.get_last_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &stable_vault.address), Some(now));
+    assert_eq!(automated_savings_client.next_eligible(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &stable_vault.address), now + MONTH_IN_SECONDS);
+    assert_eq!(automated_savings_client.remaining_allowance(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &stable_vault.address), vec![&env, 0]);
+
+    // 2025-02-01
+    assert_eq!(automated_savings_client.next_eligible(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &xlm_vault.address), JANUARY_2025 + 31*DAY_IN_SECONDS);
+
+    let eligibility = automated_savings_client.get_eligibility(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert!(eligibility.contains(&Eligibility {
+        vault: xlm_vault.address.clone(),
+        last_deposit: Some(now),
+        next_eligible: JANUARY_2025 + 31*DAY_IN_SECONDS,
+        remaining: vec![&env, 0],
+    }));
+
+    let failed_query = automated_savings_client.try_next_eligible(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &Address::generate(&env));
+    assert_eq!(failed_query, Err(Ok(SorobanError::from(Error::NotFound))));
+}
+
+#[test]
+fn check_deposit_lists_every_failure() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+
+    let contexts = deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]);
+
+    // Checking doesn't count as a deposit
+    for _ in 0..2 {
+        assert_eq!(
+            automated_savings_client.check_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts),
+            Verdict {
+                allowed: true,
+                failures: vec![&env],
+            }
+        );
+    }
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    assert_eq!(
+        automated_savings_client.check_deposit(
+            &wallet,
+            &SignerKey::Ed25519(user_bytes.clone()),
+            &deposit_contexts_with_min(&env, &vault.address, &Address::generate(&env), vec![&env, 2*amount], vec![&env, 0]),
+        ),
+        Verdict {
+            allowed: false,
+            failures: vec![&env, Error::TooSoon, Error::TooMuch, Error::SlippageTooHigh, Error::WrongFrom],
+        }
+    );
+
+    assert_eq!(
+        automated_savings_client.check_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &Address::generate(&env), &wallet, vec![&env, amount])),
+        Verdict {
+            allowed: false,
+            failures: vec![&env, Error::WrongVault],
+        }
+    );
+
+    // Pausing doesn't hide the other failures
+    automated_savings_client.pause(&wallet);
+
+    assert_eq!(
+        automated_savings_client.check_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &Address::generate(&env), vec![&env, amount])),
+        Verdict {
+            allowed: false,
+            failures: vec![&env, Error::Paused, Error::TooSoon, Error::TooMuch, Error::WrongFrom],
+        }
+    );
+
+    assert_eq!(
+        automated_savings_client.check_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &Address::generate(&env), &wallet, vec![&env, amount])),
+        Verdict {
+            allowed: false,
+            failures: vec![&env, Error::Paused, Error::WrongVault],
+        }
+    );
+}
+
+#[test]
+fn admin_is_set_at_deploy() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    assert_eq!(automated_savings_client.get_admin(), wallet);
+
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        vec![
+            &env,
+            (
+                automated_savings_client.address.clone(),
+                (symbol_short!("zafegard"), symbol_short!("admin")).into_val(&env),
+                AdminChangedEvent {
+                    previous: None,
+                    admin: wallet.clone(),
+                }.into_val(&env),
+            ),
+        ]
+    );
+
+    // The wallet can manage savers right away, and is the one authorizing it
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&create_test_vault(&env).0.address, vec![&env, 100], Schedule::Interval(MONTH_IN_SECONDS))]);
+
+    assert_eq!(env.auths()[0].0, wallet);
+}
+
+#[test]
+fn admin_transfer_takes_two_steps() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, _) = setup(&env);
+    let new_wallet = create_wallet(&env);
+
+    let failed_accept = automated_savings_client.try_accept_admin();
+    assert_eq!(failed_accept, Err(Ok(SorobanError::from(Error::NoPendingAdmin))));
+
+    automated_savings_client.propose_admin(&new_wallet);
+    assert_eq!(env.auths()[0].0, wallet);
+    assert_eq!(automated_savings_client.get_pending_admin(), Some(new_wallet.clone()));
+
+    automated_savings_client.cancel_admin_transfer();
+
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        vec![
+            &env,
+            (
+                automated_savings_client.address.clone(),
+                (symbol_short!("zafegard"), symbol_short!("cancelled")).into_val(&env),
+                AdminTransferEvent {
+                    admin: wallet.clone(),
+                    new_admin: new_wallet.clone(),
+                }.into_val(&env),
+            ),
+        ]
+    );
+    assert_eq!(automated_savings_client.get_pending_admin(), None);
+
+    let failed_accept = automated_savings_client.try_accept_admin();
+    assert_eq!(failed_accept, Err(Ok(SorobanError::from(Error::NoPendingAdmin))));
+
+    automated_savings_client.propose_admin(&new_wallet);
+
+    // Proposing alone doesn't hand over the role
+    assert_eq!(automated_savings_client.get_admin(), wallet);
+
+    automated_savings_client.accept_admin();
+    assert_eq!(env.auths()[0].0, new_wallet);
+
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        vec![
+            &env,
+            (
+                automated_savings_client.address.clone(),
+                (symbol_short!("zafegard"), symbol_short!("admin")).into_val(&env),
+                AdminChangedEvent {
+                    previous: Some(wallet.clone()),
+                    admin: new_wallet.clone(),
+                }.into_val(&env),
+            ),
+        ]
+    );
+    assert_eq!(automated_savings_client.get_admin(), new_wallet);
+    assert_eq!(automated_savings_client.get_pending_admin(), None);
+}
+
+#[test]
+fn paused_policy_rejects_deposits() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+    let guardian = Address::generate(&env);
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+
+    let contexts = deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]);
+
+    // Only the admin or the guardian can pause
+    let failed_pause = automated_savings_client.try_pause(&guardian);
+    assert_eq!(failed_pause, Err(Ok(SorobanError::from(Error::Unauthorized))));
+
+    automated_savings_client.set_guardian(&Some(guardian.clone()));
+    assert_eq!(automated_savings_client.get_guardian(), Some(guardian.clone()));
+
+    automated_savings_client.pause(&guardian);
+    assert_eq!(env.auths()[0].0, guardian);
+    assert!(automated_savings_client.is_paused());
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::Paused))));
+
+    // The guardian has no say in unpausing
+    automated_savings_client.unpause();
+    assert_eq!(env.auths()[0].0, wallet);
+    assert!(!automated_savings_client.is_paused());
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+}
+
+#[test]
+fn frozen_wallet_keeps_its_state() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+
+    let failed_freeze = automated_savings_client.try_freeze_wallet(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert_eq!(failed_freeze, Err(Ok(SorobanError::from(Error::NotFound))));
+
+    let allowance = vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS));
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, allowance.clone()]);
+
+    let contexts = deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]);
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    let last_deposit = env.ledger().timestamp();
+
+    automated_savings_client.freeze_wallet(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert!(automated_savings_client.is_frozen(&wallet, &SignerKey::Ed25519(user_bytes.clone())));
+
+    // The wallet can't lift the freeze by removing and adding the user again
+    let failed_remove = automated_savings_client.try_remove_wallet(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert_eq!(failed_remove, Err(Ok(SorobanError::from(Error::Frozen))));
+
+    let failed_add = automated_savings_client.try_add_wallet(
+        &wallet,
+        &SaverSigner::Ed25519(user_bytes.clone()),
+        &vec![&env, allowance.clone()],
+        &None,
+        &SignerStorage::Persistent,
+    );
+    assert_eq!(failed_add, Err(Ok(SorobanError::from(Error::Frozen))));
+
+    env.ledger().set_timestamp(3*MONTH_IN_SECONDS);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::Frozen))));
+
+    assert_eq!(automated_savings_client.get_allowance(&wallet, &SignerKey::Ed25519(user_bytes.clone())), vec![&env, allowance]);
+    assert_eq!(automated_savings_client.get_last_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &vault.address), Some(last_deposit));
+
+    automated_savings_client.unfreeze_wallet(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert!(!automated_savings_client.is_frozen(&wallet, &SignerKey::Ed25519(user_bytes.clone())));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+}
+
+#[test]
+fn ttl_is_extended() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    env.ledger().set_min_persistent_entry_ttl(50);
+
+    let vault = create_test_vault(&env).0.address;
+    let amount = 100;
+
+    let config = TtlConfig {
+        instance_threshold: 100,
+        instance_extend_to: 5000,
+        persistent_threshold: 100,
+        persistent_extend_to: 2000,
+    };
+
+    let failed_config = automated_savings_client.try_set_ttl_config(&TtlConfig {
+        persistent_threshold: 3000,
+        ..config.clone()
+    });
+    assert_eq!(failed_config, Err(Ok(SorobanError::from(Error::InvalidTtl))));
+
+    automated_savings_client.set_ttl_config(&config);
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        vec![
+            &env,
+            (
+                automated_savings_client.address.clone(),
+                (symbol_short!("zafegard"), symbol_short!("ttl")).into_val(&env),
+                config.clone().into_val(&env),
+            ),
+        ]
+    );
+
+    assert_eq!(automated_savings_client.get_ttl_config(), config);
+
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault, &wallet, vec![&env, amount]));
+
+    let allowances_key = StorageKey::Allowances(wallet.clone(), SignerKey::Ed25519(user_bytes.clone()));
+    let previous_key = StorageKey::Previous(wallet.clone(), SignerKey::Ed25519(user_bytes.clone()), vault.clone());
+
+    env.as_contract(&automated_savings_client.address, || {
+        assert_eq!(env.storage().persistent().get_ttl(&allowances_key), 2000);
+        assert_eq!(env.storage().persistent().get_ttl(&previous_key), 2000);
+    });
+
+    // A keeper keeps an inactive user alive
+    env.ledger().set_sequence_number(env.ledger().sequence() + 1950);
+
+    automated_savings_client.bump_user(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+
+    env.as_contract(&automated_savings_client.address, || {
+        assert_eq!(env.storage().persistent().get_ttl(&allowances_key), 2000);
+        assert_eq!(env.storage().persistent().get_ttl(&previous_key), 2000);
+    });
+}
+
+#[test]
+fn temporary_signers_lapse_gracefully() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    automated_savings_client.set_ttl_config(&TtlConfig {
+        instance_threshold: 100,
+        instance_extend_to: 5000,
+        persistent_threshold: 100,
+        persistent_extend_to: 2000,
+    });
+
+    let vault = create_test_vault(&env).0.address;
+    let amount = 100;
+
+    automated_savings_client.add_wallet(
+        &wallet,
+        &SaverSigner::Ed25519(user_bytes.clone()),
+        &vec![&env, vault_allowance(&vault, vec![&env, amount], Schedule::Interval(WEEK_IN_SECONDS))],
+        &None,
+        &SignerStorage::Temporary,
+    );
+    assert_eq!(automated_savings_client.get_storage(&wallet, &SignerKey::Ed25519(user_bytes.clone())), SignerStorage::Temporary);
+
+    let contexts = deposit_contexts(&env, &vault, &wallet, vec![&env, amount]);
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    let allowances_key = StorageKey::Allowances(wallet.clone(), SignerKey::Ed25519(user_bytes.clone()));
+    let previous_key = StorageKey::Previous(wallet.clone(), SignerKey::Ed25519(user_bytes.clone()), vault.clone());
+
+    env.as_contract(&automated_savings_client.address, || {
+        assert!(!env.storage().persistent().has(&allowances_key));
+        assert_eq!(env.storage().temporary().get_ttl(&allowances_key), 2000);
+        assert_eq!(env.storage().temporary().get_ttl(&previous_key), 2000);
+    });
+
+    // Every entry lapses at the same ledger, so the cooldown can't go before the allowance
+    env.ledger().set_sequence_number(env.ledger().sequence() + 1000);
+    env.ledger().set_timestamp(2*MONTH_IN_SECONDS + WEEK_IN_SECONDS);
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    env.as_contract(&automated_savings_client.address, || {
+        assert_eq!(env.storage().temporary().get_ttl(&allowances_key), 2000);
+        assert_eq!(env.storage().temporary().get_ttl(&previous_key), 2000);
+    });
+
+    env.ledger().set_sequence_number(env.ledger().sequence() + 2001);
+    env.ledger().set_timestamp(2*MONTH_IN_SECONDS + 2*WEEK_IN_SECONDS);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::Lapsed))));
+
+    let failed_query = automated_savings_client.try_get_allowance(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert_eq!(failed_query, Err(Ok(SorobanError::from(Error::Lapsed))));
+
+    // The lapsed user can still be removed from the wallet
+    automated_savings_client.remove_wallet(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+
+    let failed_query = automated_savings_client.try_get_allowance(&wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert_eq!(failed_query, Err(Ok(SorobanError::from(Error::NotFound))));
+
+    // Switching storages doesn't leave the old entries behind
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault, vec![&env, amount], Schedule::Interval(WEEK_IN_SECONDS))]);
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    SmartWalletClient::new(&env, &wallet).remove_signer(&SignerKey::Ed25519(user_bytes.clone()));
+    automated_savings_client.add_wallet(
+        &wallet,
+        &SaverSigner::Ed25519(user_bytes.clone()),
+        &vec![&env, vault_allowance(&vault, vec![&env, amount], Schedule::Interval(WEEK_IN_SECONDS))],
+        &None,
+        &SignerStorage::Temporary,
+    );
+
+    env.as_contract(&automated_savings_client.address, || {
+        assert!(!env.storage().persistent().has(&allowances_key));
+        assert!(!env.storage().persistent().has(&previous_key));
+        assert!(env.storage().temporary().has(&allowances_key));
+        assert!(!env.storage().temporary().has(&previous_key));
+    });
+}
+
+#[test]
+fn migrate_converts_v0_entries() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    env.ledger().set_sequence_number(10_000);
+
+    assert_eq!(automated_savings_client.get_version(), 1);
+
+    let vault = Address::generate(&env);
+    let amount = 100;
+
+    // What a deployment from before the storage version was introduced holds
+    env.as_contract(&automated_savings_client.address, || {
+        env.storage().instance().remove(&StorageKey::Version);
+        env.storage().persistent().set(
+            &StorageKeyV0::VaultAllowance(user_bytes.clone()),
+            &VaultAllowanceV0 {
+                vault: vault.clone(),
+                amount,
+            },
+        );
+        // One hour of 5 second ledgers ago
+        env.storage().persistent().set(&StorageKeyV0::Previous(user_bytes.clone()), &(10_000u32 - 720));
+    });
+
+    assert_eq!(automated_savings_client.get_version(), 0);
+
+    automated_savings_client.migrate(&wallet, &vec![&env, user_bytes.clone()]);
+
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        vec![
+            &env,
+            (
+                automated_savings_client.address.clone(),
+                (symbol_short!("zafegard"), symbol_short!("migrated"), wallet.clone()).into_val(&env),
+                UserEvent {
+                    wallet: wallet.clone(),
+                    user: SignerKey::Ed25519(user_bytes.clone()),
+                }.into_val(&env),
+            ),
+        ]
+    );
+
+    let mut allowance = vault_allowance(&vault, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS));
+    allowance.max_slippage_bps = 10_000;
+    assert_eq!(automated_savings_client.get_allowance(&wallet, &SignerKey::Ed25519(user_bytes.clone())), vec![&env, allowance.clone()]);
+    assert_eq!(automated_savings_client.get_last_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &vault), Some(2*MONTH_IN_SECONDS - 3600));
+
+    env.as_contract(&automated_savings_client.address, || {
+        assert!(!env.storage().persistent().has(&StorageKeyV0::VaultAllowance(user_bytes.clone())));
+        assert!(!env.storage().persistent().has(&StorageKeyV0::Previous(user_bytes.clone())));
+    });
+
+    automated_savings_client.finish_migration();
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        vec![
+            &env,
+            (
+                automated_savings_client.address.clone(),
+                (symbol_short!("zafegard"), symbol_short!("finished")).into_val(&env),
+                1u32.into_val(&env),
+            ),
+        ]
+    );
+
+    assert_eq!(automated_savings_client.get_version(), 1);
+
+    // The cooldown carried over from the old ledger-based marker
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault, &wallet, vec![&env, amount]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
+
+    // A user missed by the migration can still go through it afterwards
+    let late_user = Address::generate(&env);
+    let late_user_bytes = address_to_bytes(&env, &late_user);
+
+    env.as_contract(&automated_savings_client.address, || {
+        env.storage().persistent().set(
+            &StorageKeyV0::VaultAllowance(late_user_bytes.clone()),
+            &VaultAllowanceV0 {
+                vault: vault.clone(),
+                amount,
+            },
+        );
+    });
+
+    let failed_query = automated_savings_client.try_get_allowance(&wallet, &SignerKey::Ed25519(late_user_bytes.clone()));
+    assert_eq!(failed_query, Err(Ok(SorobanError::from(Error::NotFound))));
+
+    automated_savings_client.migrate(&wallet, &vec![&env, late_user_bytes.clone()]);
+    assert_eq!(automated_savings_client.get_allowance(&wallet, &SignerKey::Ed25519(late_user_bytes)), vec![&env, allowance]);
+}
+
+#[test]
+fn one_policy_serves_many_wallets() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+    let other_wallet = create_wallet(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+
+    // The same signer is registered in both wallets
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, 100], Schedule::Interval(MONTH_IN_SECONDS))]);
+    add_saver(&automated_savings_client, &other_wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, 50], Schedule::Interval(WEEK_IN_SECONDS))]);
+
+    // Each wallet manages its own signers
+    assert_eq!(env.auths()[0].0, other_wallet);
+
+    assert_eq!(automated_savings_client.get_allowance(&wallet, &SignerKey::Ed25519(user_bytes.clone())).get_unchecked(0).amounts, vec![&env, 100]);
+    assert_eq!(automated_savings_client.get_allowance(&other_wallet, &SignerKey::Ed25519(user_bytes.clone())).get_unchecked(0).amounts, vec![&env, 50]);
+
+    // The source picks the configuration
+    let failed_deposit = automated_savings_client.try_policy__(&other_wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &other_wallet, vec![&env, 100]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooMuch))));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, 100]));
+    automated_savings_client.policy__(&other_wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &other_wallet, vec![&env, 50]));
+
+    // Removing the signer from one wallet leaves the other untouched
+    automated_savings_client.remove_wallet(&other_wallet, &SignerKey::Ed25519(user_bytes.clone()));
+
+    let failed_query = automated_savings_client.try_get_allowance(&other_wallet, &SignerKey::Ed25519(user_bytes.clone()));
+    assert_eq!(failed_query, Err(Ok(SorobanError::from(Error::NotFound))));
+    assert_eq!(automated_savings_client.get_last_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &vault.address), Some(2*MONTH_IN_SECONDS));
+
+    // Nor can the removed signer deposit from it anymore
+    let failed_deposit = automated_savings_client.try_policy__(&other_wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &other_wallet, vec![&env, 50]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::NotFound))));
+
+    // Only the wallet itself can spend its allowance
+    env.ledger().set_timestamp(3*MONTH_IN_SECONDS);
+    env.set_auths(&[]);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, 100]));
+    assert!(failed_deposit.is_err());
+    assert_eq!(automated_savings_client.get_last_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &vault.address), Some(2*MONTH_IN_SECONDS));
+
+    env.mock_all_auths();
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, 100]));
+}
+
+#[test]
+fn passkey_signers_are_enforced() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let (new_vault, _, _, _, _, _) = create_test_vault(&env);
+
+    // A second device passkey next to the session key
+    let passkey_id = Bytes::from_array(&env, &[7; 20]);
+    let passkey = SaverSigner::Secp256r1(passkey_id.clone(), BytesN::<65>::random(&env));
+    let amount = 100;
+
+    automated_savings_client.add_wallet(&wallet, &passkey, &vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))], &None, &SignerStorage::Persistent);
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, 2*amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+
+    let contexts = deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Secp256r1(passkey_id.clone()), &deposit_contexts(&env, &vault.address, &wallet, vec![&env, 2*amount]));
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooMuch))));
+
+    automated_savings_client.policy__(&wallet, &SignerKey::Secp256r1(passkey_id.clone()), &contexts);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Secp256r1(passkey_id.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::TooSoon))));
+
+    // The session key keeps its own cooldown
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    // Policies never deposit on their own
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Policy(automated_savings_client.address.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::NotAllowed))));
+
+    automated_savings_client.update_wallet(&wallet, &passkey, &vec![&env, vault_allowance(&new_vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+    automated_savings_client.policy__(&wallet, &SignerKey::Secp256r1(passkey_id.clone()), &deposit_contexts(&env, &new_vault.address, &wallet, vec![&env, amount]));
+
+    automated_savings_client.remove_wallet(&wallet, &SignerKey::Secp256r1(passkey_id.clone()));
+
+    let failed_query = automated_savings_client.try_get_allowance(&wallet, &SignerKey::Secp256r1(passkey_id));
+    assert_eq!(failed_query, Err(Ok(SorobanError::from(Error::NotFound))));
+    assert_eq!(automated_savings_client.get_last_deposit(&wallet, &SignerKey::Ed25519(user_bytes), &vault.address), Some(2*MONTH_IN_SECONDS));
+}
+
+#[test]
+fn expired_signers_cant_deposit() {
+    let env = Env::new_with_config(EnvTestConfig { capture_snapshot_at_drop: false });
+    let (wallet, automated_savings_client, user_bytes) = setup(&env);
+
+    env.ledger().set_sequence_number(10_000);
+
+    let (vault, _, _, _, _, _) = create_test_vault(&env);
+    let amount = 100;
+    let allowance = vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(WEEK_IN_SECONDS));
+
+    let failed_add = automated_savings_client.try_add_wallet(&wallet, &SaverSigner::Ed25519(user_bytes.clone()), &vec![&env, allowance.clone()], &Some(9_999), &SignerStorage::Persistent);
+    assert_eq!(failed_add, Err(Ok(SorobanError::from(Error::InvalidExpiration))));
+
+    automated_savings_client.add_wallet(&wallet, &SaverSigner::Ed25519(user_bytes.clone()), &vec![&env, allowance.clone()], &Some(10_100), &SignerStorage::Persistent);
+    assert_eq!(automated_savings_client.get_expiration(&wallet, &SignerKey::Ed25519(user_bytes.clone())), Some(10_100));
+
+    let contexts = deposit_contexts(&env, &vault.address, &wallet, vec![&env, amount]);
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    // Updates keep the expiration
+    let mut new_allowance = allowance.clone();
+    new_allowance.amounts = vec![&env, 2*amount];
+    automated_savings_client.update_wallet(&wallet, &SaverSigner::Ed25519(user_bytes.clone()), &vec![&env, new_allowance]);
+    assert_eq!(automated_savings_client.get_expiration(&wallet, &SignerKey::Ed25519(user_bytes.clone())), Some(10_100));
+
+    // Still usable on the expiration ledger itself
+    env.ledger().set_timestamp(2*MONTH_IN_SECONDS + WEEK_IN_SECONDS);
+    env.ledger().set_sequence_number(10_100);
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+
+    env.ledger().set_timestamp(2*MONTH_IN_SECONDS + 2*WEEK_IN_SECONDS);
+    env.ledger().set_sequence_number(10_101);
+
+    let failed_deposit = automated_savings_client.try_policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(failed_deposit, Err(Ok(SorobanError::from(Error::Expired))));
+
+    let verdict = automated_savings_client.check_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+    assert_eq!(verdict.failures, vec![&env, Error::Expired]);
+
+    // The expired signer is dropped by the wallet itself, and added again later on
+    // a monthly plan without expiration
+    SmartWalletClient::new(&env, &wallet).remove_signer(&SignerKey::Ed25519(user_bytes.clone()));
+    add_saver(&automated_savings_client, &wallet, &user_bytes, vec![&env, vault_allowance(&vault.address, vec![&env, amount], Schedule::Interval(MONTH_IN_SECONDS))]);
+    assert_eq!(automated_savings_client.get_expiration(&wallet, &SignerKey::Ed25519(user_bytes.clone())), None);
+    assert_eq!(automated_savings_client.get_last_deposit(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &vault.address), None);
+
+    // Neither the old expiration nor the last weekly deposit apply anymore
+    automated_savings_client.policy__(&wallet, &SignerKey::Ed25519(user_bytes.clone()), &contexts);
+}
 
 #[test]
 fn test_create_vault() {
@@ -405,6 +1541,37 @@ fn test_create_vault() {
 //     );
 // }
 
+fn vault_allowance(vault: &Address, amounts: Vec<i128>, schedule: Schedule) -> VaultAllowance {
+    VaultAllowance {
+        vault: vault.clone(),
+        amounts,
+        schedule,
+        max_slippage_bps: 0,
+        max_carry_over: 0,
+    }
+}
+
+fn deposit_contexts(env: &Env, vault: &Address, from: &Address, amounts: Vec<i128>) -> Vec<Context> {
+    deposit_contexts_with_min(env, vault, from, amounts.clone(), amounts)
+}
+
+fn deposit_contexts_with_min(env: &Env, vault: &Address, from: &Address, amounts: Vec<i128>, min_amounts: Vec<i128>) -> Vec<Context> {
+    vec![
+        env,
+        Context::Contract(ContractContext {
+            contract: vault.clone(),
+            fn_name: symbol_short!("deposit"),
+            args: vec![
+                env,
+                amounts.try_into_val(env).unwrap(), // amounts
+                min_amounts.try_into_val(env).unwrap(), // min_amounts
+                from.to_val(),      // from
+                false.into_val(env),     // claim
+            ],
+        }),
+    ]
+}
+
 fn address_to_bytes(env: &Env, address: &Address) -> BytesN<32> {
     let mut address_array = [0; 32];
     let address_bytes = address.to_xdr(env);
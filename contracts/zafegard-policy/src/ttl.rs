@@ -0,0 +1,85 @@
+use smart_wallet_interface::types::{SignerKey, SignerStorage};
+use soroban_sdk::{Address, Env, Map};
+
+use crate::{storage, StorageKey, TtlConfig, VaultAllowance};
+
+const DAY_IN_LEDGERS: u32 = 17_280;
+
+pub const DEFAULT_TTL_CONFIG: TtlConfig = TtlConfig {
+    instance_threshold: 7 * DAY_IN_LEDGERS,
+    instance_extend_to: 30 * DAY_IN_LEDGERS,
+    persistent_threshold: 30 * DAY_IN_LEDGERS,
+    persistent_extend_to: 120 * DAY_IN_LEDGERS,
+};
+
+pub fn ttl_config(env: &Env) -> TtlConfig {
+    env.storage()
+        .instance()
+        .get::<StorageKey, TtlConfig>(&StorageKey::TtlConfig)
+        .unwrap_or(DEFAULT_TTL_CONFIG)
+}
+
+// Extends the admin, pause and config entries along with the contract code
+pub fn extend_instance(env: &Env) {
+    let config = ttl_config(env);
+
+    env.storage()
+        .instance()
+        .extend_ttl(config.instance_threshold, config.instance_extend_to);
+}
+
+// Extends every entry of a user
+pub fn extend_user(env: &Env, wallet: &Address, user: &SignerKey) {
+    let storage = storage::user_storage(env, wallet, user);
+    let key = StorageKey::Allowances(wallet.clone(), user.clone());
+
+    if let Some(allowances) = storage::get::<Map<Address, VaultAllowance>>(env, &storage, &key) {
+        extend_entry(env, &SignerStorage::Persistent, &StorageKey::Storage(wallet.clone(), user.clone()));
+        extend_entry(env, &storage, &key);
+        extend_entry(env, &storage, &StorageKey::Frozen(wallet.clone(), user.clone()));
+        extend_entry(env, &storage, &StorageKey::Expiration(wallet.clone(), user.clone()));
+        for vault in allowances.keys().iter() {
+            extend_vault(env, &storage, wallet, user, &vault);
+        }
+    }
+}
+
+// Extends the user entries policy__ reads when depositing into a vault
+pub fn extend_user_vault(env: &Env, wallet: &Address, user: &SignerKey, vault: &Address) {
+    let storage = storage::user_storage(env, wallet, user);
+
+    // Temporary entries are always extended all at once, see extend_entry
+    if let SignerStorage::Temporary = storage {
+        return extend_user(env, wallet, user);
+    }
+
+    extend_entry(env, &storage, &StorageKey::Allowances(wallet.clone(), user.clone()));
+    extend_entry(env, &storage, &StorageKey::Frozen(wallet.clone(), user.clone()));
+    extend_entry(env, &storage, &StorageKey::Expiration(wallet.clone(), user.clone()));
+    extend_vault(env, &storage, wallet, user, vault);
+}
+
+fn extend_vault(env: &Env, storage: &SignerStorage, wallet: &Address, user: &SignerKey, vault: &Address) {
+    extend_entry(env, storage, &StorageKey::Previous(wallet.clone(), user.clone(), vault.clone()));
+    extend_entry(env, storage, &StorageKey::Spent(wallet.clone(), user.clone(), vault.clone()));
+}
+
+fn extend_entry(env: &Env, storage: &SignerStorage, key: &StorageKey) {
+    if !storage::has(env, storage, key) {
+        return;
+    }
+
+    let config = ttl_config(env);
+    match storage {
+        SignerStorage::Persistent => env
+            .storage()
+            .persistent()
+            .extend_ttl(key, config.persistent_threshold, config.persistent_extend_to),
+        // Lapsed temporary entries are gone for good, so every entry of a user is
+        // extended to the same ledger and they all lapse together
+        SignerStorage::Temporary => env
+            .storage()
+            .temporary()
+            .extend_ttl(key, config.persistent_extend_to, config.persistent_extend_to),
+    }
+}
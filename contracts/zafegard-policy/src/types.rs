@@ -1,9 +1,40 @@
-use soroban_sdk::{contracttype, Address, Bytes, BytesN};
+use smart_wallet_interface::types::{Signer, SignerExpiration, SignerKey, SignerLimits, SignerStorage};
+use soroban_sdk::{contracttype, Bytes, BytesN};
 
+// A signer along with the public key the smart wallet verifies it with
 #[contracttype]
 #[derive(Clone, Debug, PartialEq)]
-pub enum SignerKey {
-    Policy(Address),
+pub enum SaverSigner {
+    // Session key
     Ed25519(BytesN<32>),
-    Secp256r1(Bytes),
+    // Passkey id and public key
+    Secp256r1(Bytes, BytesN<65>),
+}
+
+impl SaverSigner {
+    // Identifies the user in the smart wallet and in policy__
+    pub fn key(&self) -> SignerKey {
+        match self {
+            SaverSigner::Ed25519(public_key) => SignerKey::Ed25519(public_key.clone()),
+            SaverSigner::Secp256r1(id, _) => SignerKey::Secp256r1(id.clone()),
+        }
+    }
+
+    pub fn to_signer(&self, expiration: SignerExpiration, limits: SignerLimits, storage: SignerStorage) -> Signer {
+        match self {
+            SaverSigner::Ed25519(public_key) => Signer::Ed25519(
+                public_key.clone(),
+                expiration,
+                limits,
+                storage,
+            ),
+            SaverSigner::Secp256r1(id, public_key) => Signer::Secp256r1(
+                id.clone(),
+                public_key.clone(),
+                expiration,
+                limits,
+                storage,
+            ),
+        }
+    }
 }